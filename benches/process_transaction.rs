@@ -0,0 +1,83 @@
+//! Throughput of `PaymentEngine::process_transaction`, run with `cargo bench`
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use payment_toy::{
+    account::{Action, Client, Money, Transaction, TxId},
+    PaymentEngine,
+};
+
+const CLIENTS: Client = 1000;
+const HISTORY: TxId = 100_000;
+
+/// An engine with a large history of deposits, spread across every client
+fn engine_with_history() -> PaymentEngine {
+    let mut engine = PaymentEngine::new();
+    let amount: Money = "100.0".parse().unwrap();
+    for id in 0..HISTORY {
+        let tx = Transaction::new(Action::try_new_deposit(amount).unwrap(), client_of(id), id);
+        engine.process_transaction(&tx).unwrap();
+    }
+    engine
+}
+
+fn client_of(id: TxId) -> Client {
+    (id % CLIENTS as TxId) as Client
+}
+
+fn mixed_actions(c: &mut Criterion) {
+    let mut engine = engine_with_history();
+    let deposit: Money = "10.5".parse().unwrap();
+    let withdrawal: Money = "2.25".parse().unwrap();
+    let mut next_id = HISTORY;
+    let mut past_id = 0;
+
+    let mut group = c.benchmark_group("process_transaction");
+    group.throughput(Throughput::Elements(4));
+    group.bench_function("mixed", |b| {
+        b.iter(|| {
+            // a deposit and withdrawal with fresh ids, and a dispute and resolve of a past deposit
+            let txs = [
+                Transaction::new(
+                    Action::try_new_deposit(deposit).unwrap(),
+                    client_of(next_id),
+                    next_id,
+                ),
+                Transaction::new(
+                    Action::try_new_withdrawal(withdrawal).unwrap(),
+                    client_of(next_id + 1),
+                    next_id + 1,
+                ),
+                Transaction::new(Action::new_dispute(), client_of(past_id), past_id),
+                Transaction::new(Action::new_resolve(), client_of(past_id), past_id),
+            ];
+            for tx in &txs {
+                let _ = black_box(engine.process_transaction(tx));
+            }
+            next_id += 2;
+            past_id = (past_id + 1) % HISTORY;
+        })
+    });
+    group.finish();
+}
+
+fn dispute_path(c: &mut Criterion) {
+    let mut engine = engine_with_history();
+    let mut past_id = 0;
+
+    let mut group = c.benchmark_group("process_transaction");
+    group.throughput(Throughput::Elements(2));
+    group.bench_function("dispute_and_resolve", |b| {
+        b.iter(|| {
+            // resolving straight after leaves the history as it was, ready for the next dispute
+            let client = client_of(past_id);
+            let dispute = Transaction::new(Action::new_dispute(), client, past_id);
+            let resolve = Transaction::new(Action::new_resolve(), client, past_id);
+            black_box(engine.process_transaction(&dispute)).unwrap();
+            black_box(engine.process_transaction(&resolve)).unwrap();
+            past_id = (past_id + 1) % HISTORY;
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, mixed_actions, dispute_path);
+criterion_main!(benches);
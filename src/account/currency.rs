@@ -0,0 +1,135 @@
+//! The scales of currencies, the decimal places their amounts are kept to, for when amounts
+//! of more than one currency are kept as [`Money`]
+//!
+use super::{Money, MoneyParseError};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::io;
+
+/// ISO 4217 currencies with fewer minor units than [`Money::SCALE`], by code.
+/// Any other currency is kept to the full scale, as a single currency always is.
+const SCALES: &[(&str, usize)] = &[
+    // no minor units
+    ("CLP", 0),
+    ("ISK", 0),
+    ("JPY", 0),
+    ("KRW", 0),
+    ("VND", 0),
+    // thousandths
+    ("BHD", 3),
+    ("IQD", 3),
+    ("JOD", 3),
+    ("KWD", 3),
+    ("LYD", 3),
+    ("OMR", 3),
+    ("TND", 3),
+    // hundredths
+    ("EUR", 2),
+    ("GBP", 2),
+    ("USD", 2),
+];
+
+/// The number of decimal places amounts of the `currency`, such as `"JPY"`, are kept to,
+/// regardless of the code's case
+pub fn scale_of(currency: &str) -> usize {
+    SCALES
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(currency))
+        .map_or(Money::SCALE, |&(_, scale)| scale)
+}
+
+/// Parses `amount` of the `currency`, rounding it to the currency's scale
+pub fn parse_amount(currency: &str, amount: &str) -> Result<Money, MoneyParseError> {
+    Money::parse_at_scale(amount, scale_of(currency))
+}
+
+/// Writes `amount` of the `currency` with exactly the currency's decimal places,
+/// such as `"1500"` yen or `"1.250"` dinars
+pub fn format_amount(currency: &str, amount: Money) -> String {
+    format!("{:.*}", scale_of(currency), amount)
+}
+
+/// Are `a` and `b` the same amount of the `currency`, once rounded to its scale?
+/// `None` if either can't be rounded, being within half a minor unit of [`Money::MAX`]
+/// or [`Money::MIN`].
+pub fn same_amount(currency: &str, a: Money, b: Money) -> Option<bool> {
+    let scale = scale_of(currency);
+    Some(a.round_to_scale(scale)? == b.round_to_scale(scale)?)
+}
+
+/// Exchange rates into a single reporting currency, each the value of one unit of a currency
+/// in the reporting currency, for summarizing balances of several currencies as one total.
+///
+/// Such totals are derived, and only as exact as the rates, so are no substitute for the
+/// balances of each currency.
+#[derive(Clone, Debug, Default)]
+pub struct Rates(HashMap<String, Money>);
+
+/// Why balances couldn't be converted by [`Rates::equivalent`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConversionError {
+    /// a balance's currency has no rate
+    MissingRate(String),
+    /// a converted balance, or their sum, is too large to be represented
+    Overflow,
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConversionError::MissingRate(currency) => {
+                write!(f, "no rate for currency {:?}", currency)
+            }
+            ConversionError::Overflow => {
+                f.write_str("converted balances too large to be represented")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+impl Rates {
+    /// Reads rates from CSV with the columns `currency` and `rate`, as in `EUR,1.08`.
+    /// Currencies are matched regardless of case.
+    pub fn read<R: io::Read>(reader: R) -> Result<Self, csv::Error> {
+        #[derive(serde::Deserialize)]
+        struct Row {
+            currency: String,
+            rate: Money,
+        }
+        let mut csv_in = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let mut rates = HashMap::new();
+        for row in csv_in.deserialize() {
+            let Row { currency, rate } = row?;
+            rates.insert(currency.to_ascii_uppercase(), rate);
+        }
+        Ok(Self(rates))
+    }
+
+    /// The rate of the `currency` into the reporting currency
+    pub fn rate(&self, currency: &str) -> Option<Money> {
+        self.0.get(&currency.to_ascii_uppercase()).copied()
+    }
+
+    /// The sum of `balances`, each an amount of a currency, in the reporting currency,
+    /// or an error if a currency has no rate or the conversion overflows.
+    pub fn equivalent<'a>(
+        &self,
+        balances: impl IntoIterator<Item = (&'a str, Money)>,
+    ) -> Result<Money, ConversionError> {
+        balances
+            .into_iter()
+            .try_fold(Money::ZERO, |sum, (currency, amount)| {
+                let rate = self
+                    .rate(currency)
+                    .ok_or_else(|| ConversionError::MissingRate(currency.to_owned()))?;
+                amount
+                    .checked_mul(rate)
+                    .and_then(|converted| sum.checked_add(converted))
+                    .ok_or(ConversionError::Overflow)
+            })
+    }
+}
@@ -0,0 +1,19 @@
+//! `ActionHandler` applies transaction types beyond those of [`Action`](super::Action),
+//! so that downstream crates may add their own without modifying it.
+//!
+use super::{Account, Error, Transaction, TxHistory};
+
+/// Applies a custom transaction type, registered by its `type` with
+/// [`PaymentEngine::register_action`](crate::engine::PaymentEngine::register_action).
+///
+/// The transaction's action is an [`Action::Custom`](super::Action::Custom), with the
+/// record's `amount`, if any. Handlers typically apply it as built in transactions, such as
+/// a bonus as a deposit, through [`Account::process_transaction`].
+pub trait ActionHandler: Send + Sync {
+    fn apply(
+        &self,
+        account: &mut Account,
+        tx: &Transaction,
+        history: &mut TxHistory,
+    ) -> Result<(), Error>;
+}
@@ -0,0 +1,35 @@
+//! `HashState` builds the hashers of the [`TxHistory`](super::TxHistory) and of the
+//! engine's accounts, so that their iteration order may be made reproducible.
+//!
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::hash::{BuildHasher, Hasher};
+
+/// Randomly seeded by default, like the standard [`HashMap`](std::collections::HashMap),
+/// or seeded with a fixed number so that every run iterates in the same order,
+/// at the cost of the random seed's resistance to collision attacks.
+#[derive(Clone, Debug)]
+pub enum HashState {
+    Random(RandomState),
+    Seeded(u64),
+}
+
+impl Default for HashState {
+    fn default() -> Self {
+        HashState::Random(RandomState::new())
+    }
+}
+
+impl BuildHasher for HashState {
+    type Hasher = DefaultHasher;
+
+    fn build_hasher(&self) -> DefaultHasher {
+        match self {
+            HashState::Random(state) => state.build_hasher(),
+            HashState::Seeded(seed) => {
+                let mut hasher = DefaultHasher::new();
+                hasher.write_u64(*seed);
+                hasher
+            }
+        }
+    }
+}
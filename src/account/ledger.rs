@@ -0,0 +1,57 @@
+//! `Ledger` abstracts over the storage of completed transactions, so that
+//! histories too large for memory may be kept elsewhere, such as in an embedded database.
+//!
+use super::{CompletedTx, TxHistory, TxId};
+use std::collections::{hash_map::Entry, HashMap};
+
+/// Storage for the transactions an [`Account`](super::Account) has completed,
+/// which it refers back to for disputes, resolutions, and chargebacks.
+///
+/// [`TxHistory`](super::TxHistory) is the default, in-memory, implementation.
+pub trait Ledger {
+    /// Records a newly completed transaction.
+    ///
+    /// Returns `false`, recording nothing, if a transaction with `id` has already been recorded.
+    fn record(&mut self, id: TxId, tx: CompletedTx) -> bool;
+
+    /// The previously recorded transaction with `id`, to be updated in place.
+    fn get_mut(&mut self, id: TxId) -> Option<&mut CompletedTx>;
+}
+
+/// A [`Ledger`] over a borrowed [`TxHistory`], recording and updating copies of its
+/// transactions rather than the history itself, such as to simulate a transaction.
+pub(crate) struct Overlay<'a> {
+    history: &'a TxHistory,
+    changed: HashMap<TxId, CompletedTx>,
+}
+
+impl<'a> Overlay<'a> {
+    pub fn new(history: &'a TxHistory) -> Self {
+        Self {
+            history,
+            changed: HashMap::new(),
+        }
+    }
+}
+
+impl Ledger for Overlay<'_> {
+    fn record(&mut self, id: TxId, tx: CompletedTx) -> bool {
+        if self.history.contains(id) {
+            return false;
+        }
+        match self.changed.entry(id) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(v) => {
+                v.insert(tx);
+                true
+            }
+        }
+    }
+
+    fn get_mut(&mut self, id: TxId) -> Option<&mut CompletedTx> {
+        match self.changed.entry(id) {
+            Entry::Occupied(o) => Some(o.into_mut()),
+            Entry::Vacant(v) => Some(v.insert(self.history.get(id)?.clone())),
+        }
+    }
+}
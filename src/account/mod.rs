@@ -1,25 +1,48 @@
+//! Accounts and operations that can be performed on them
+pub mod currency;
+mod handler;
+mod hash_state;
+mod ledger;
 mod money;
+mod policy;
+mod reverse;
 #[cfg(test)]
 mod tests;
-///! Accounts and operations that can be performed on them
 mod transaction;
 mod tx_history;
 
-pub use money::Money;
-use serde::{ser::SerializeStruct, Serialize};
-pub use transaction::{Id as TxId, Transaction};
-pub use tx_history::TxHistory;
+pub use handler::ActionHandler;
+pub use hash_state::HashState;
+pub use ledger::Ledger;
+pub(crate) use ledger::Overlay;
+pub use money::{Money, MoneyParseError};
+pub use policy::Policy;
+pub use reverse::Irreversible;
+use serde::{de, ser::SerializeStruct, Deserialize, Serialize};
+pub use transaction::{Action, Id as TxId, Transaction, ValidationError};
+pub use tx_history::{CompletedTx, CompletedTxKind, TxHistory};
 
-/// `Client` is an [`Account`]'s unique identifier
+/// `Client` is an [`Account`]'s unique identifier, a `u16` unless widened by the
+/// `client-u32` or `client-u64` feature, for upstreams with larger ids
+#[cfg(not(any(feature = "client-u32", feature = "client-u64")))]
 pub type Client = u16;
+#[cfg(all(feature = "client-u32", not(feature = "client-u64")))]
+pub type Client = u32;
+#[cfg(feature = "client-u64")]
+pub type Client = u64;
 
 /// `Account` is one's current balance and standing with the bank.
+#[derive(Clone)]
 pub struct Account {
     client: Client,
     available_funds: Money,
     held_funds: Money,
     /// are the funds frozen?
     locked: bool,
+    /// the dispute that locked the account by exceeding the policy's `max_held`
+    auto_locked_by: Option<TxId>,
+    /// closed while empty, so every further transaction is rejected
+    closed: bool,
 }
 
 impl Account {
@@ -29,99 +52,284 @@ impl Account {
             available_funds: Money::ZERO,
             held_funds: Money::ZERO,
             locked: false,
+            auto_locked_by: None,
+            closed: false,
         }
     }
+    /// Restores an account from its balances, such as from a snapshot
+    pub(crate) fn restore(
+        client: Client,
+        available: Money,
+        held: Money,
+        locked: bool,
+        closed: bool,
+    ) -> Self {
+        Self {
+            client,
+            available_funds: available,
+            held_funds: held,
+            locked,
+            auto_locked_by: None,
+            closed,
+        }
+    }
+    pub fn client(&self) -> Client {
+        self.client
+    }
+    pub fn available(&self) -> Money {
+        self.available_funds
+    }
+    pub fn held(&self) -> Money {
+        self.held_funds
+    }
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+    /// Has the account been [closed](Action::Close)?
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+    /// The dispute that locked the account, by holding more than [`Policy::max_held`].
+    ///
+    /// Not restored along with the account's balances.
+    pub fn auto_locked_by(&self) -> Option<TxId> {
+        self.auto_locked_by
+    }
+    /// `(available, held, locked)`, for asserting on an account's state at a glance
+    pub fn balances(&self) -> (Money, Money, bool) {
+        (self.available_funds, self.held_funds, self.locked)
+    }
     pub fn total(&self) -> Money {
         self.available_funds + self.held_funds
     }
     pub fn process_transaction(
         &mut self,
         tx: &Transaction,
-        tx_history: &mut TxHistory,
+        ledger: &mut dyn Ledger,
+    ) -> Result<(), Error> {
+        self.process_transaction_with(tx, ledger, &Policy::default())
+    }
+    pub fn process_transaction_with(
+        &mut self,
+        tx: &Transaction,
+        ledger: &mut dyn Ledger,
+        policy: &Policy,
     ) -> Result<(), Error> {
         use transaction::Action::*;
+        if self.closed {
+            return Err(Error::AccountClosed(tx.id()));
+        }
+        // `try_new_` constructors reject these, but the variants may be built directly
+        if let Deposit { amount } | Withdrawal { amount } | PartialDispute { amount } = tx.action()
+        {
+            if amount.is_negative() {
+                return Err(Error::NegativeAmount(tx.id()));
+            }
+        }
+        if policy.idempotent_duplicates {
+            if let Some(replayed) = self.replayed(tx, ledger) {
+                return replayed;
+            }
+        }
         match tx.action() {
             Deposit { amount } => {
-                if self.locked {
+                if self.locked && !policy.allow_deposits_when_locked {
                     return Err(Error::AccountLockedFundsFrozen(tx.id()));
                 }
-                if tx_history
-                    .record_transaction(tx.id(), amount, tx_history::CompletedTxKind::Deposit)
-                    .is_err()
-                {
+                if !policy.allows_amount(amount) {
+                    return Err(Error::AmountTooLarge(tx.id()));
+                }
+                if amount.is_zero() && policy.skip_zero_amounts {
+                    return Ok(());
+                }
+                let completed = CompletedTx::new(self.client, CompletedTxKind::Deposit, amount);
+                if !ledger.record(tx.id(), completed) {
                     return Err(Error::DuplicateTransaction(tx.id()));
-                };
+                }
                 self.available_funds += amount;
             }
-            Withdrawal { amount } => {
+            Withdrawal { .. } | WithdrawAll => {
                 if self.locked {
                     return Err(Error::AccountLockedFundsFrozen(tx.id()));
                 }
+                let amount = match tx.action() {
+                    Withdrawal { amount } => amount,
+                    // none at all, if the funds are negative due to a dispute
+                    _ => self.available_funds.max(Money::ZERO),
+                };
+                if !policy.allows_amount(amount) {
+                    return Err(Error::AmountTooLarge(tx.id()));
+                }
                 let new_available = self.available_funds - amount;
                 if new_available < Money::ZERO {
                     return Err(Error::InsufficientFundsForWithdrawal(tx.id()));
                 }
-                if tx_history
-                    .record_transaction(tx.id(), amount, tx_history::CompletedTxKind::Withdrawal)
-                    .is_err()
-                {
+                if new_available < policy.minimum_balance {
+                    return Err(Error::BelowMinimumBalance(tx.id()));
+                }
+                if amount.is_zero() && policy.skip_zero_amounts {
+                    return Ok(());
+                }
+                let completed = CompletedTx::new(self.client, CompletedTxKind::Withdrawal, amount);
+                if !ledger.record(tx.id(), completed) {
                     return Err(Error::DuplicateTransaction(tx.id()));
-                };
+                }
                 self.available_funds = new_available;
             }
-            Dispute => {
-                let past_tx = if let Some(past) = tx_history.past_transaction(tx.id()) {
+            Adjust { amount } => {
+                if !policy.allow_adjustments {
+                    return Err(Error::AdjustmentsForbidden(tx.id()));
+                }
+                // corrections apply even to locked accounts, and may leave the funds negative
+                let new_available = self
+                    .available_funds
+                    .checked_add(amount)
+                    .ok_or(Error::AdjustmentOverflows(tx.id()))?;
+                let completed = CompletedTx::new(self.client, CompletedTxKind::Adjustment, amount);
+                if !ledger.record(tx.id(), completed) {
+                    return Err(Error::DuplicateTransaction(tx.id()));
+                }
+                self.available_funds = new_available;
+            }
+            Close => {
+                if !policy.allow_closing {
+                    return Err(Error::ClosingForbidden(tx.id()));
+                }
+                // locked or not, so long as nothing is left in it
+                if !self.available_funds.is_zero() || !self.held_funds.is_zero() {
+                    return Err(Error::CloseOfNonEmptyAccount(tx.id()));
+                }
+                self.closed = true;
+            }
+            // only the engine knows the handlers of custom actions
+            Custom { .. } => return Err(Error::UnhandledAction(tx.id())),
+            Dispute | PartialDispute { .. } => {
+                // another client's transaction is as unknown to this account as a missing one
+                let past_tx = if let Some(past) = ledger
+                    .get_mut(tx.id())
+                    .filter(|past| past.client == self.client)
+                {
                     past
                 } else {
                     return Err(Error::UnknownTxReference(tx.id()));
                 };
-                use tx_history::CompletedTxKind::*;
+                use CompletedTxKind::*;
                 match past_tx.kind {
                     // disputing withdrawals is unsupported.. ignore
                     Withdrawal => return Err(Error::WithdrawalsAreIndisputable(tx.id())),
+                    Adjustment => return Err(Error::AdjustmentsAreIndisputable(tx.id())),
                     Deposit => (),
                 }
                 if past_tx.disputed {
                     return Err(Error::DuplicateDispute(tx.id()));
                 }
-                // this may lead to negative available_funds
-                let new_available = self.available_funds - past_tx.amount;
+                if past_tx.charged_back {
+                    return Err(Error::AlreadyChargedBack(tx.id()));
+                }
+                if policy.forbid_redispute && past_tx.resolved_once {
+                    return Err(Error::AlreadyResolvedOnce(tx.id()));
+                }
+                let disputed_amount = match tx.action() {
+                    PartialDispute { amount } if amount > past_tx.amount => {
+                        return Err(Error::DisputeExceedsAmount(tx.id()))
+                    }
+                    PartialDispute { amount } => amount,
+                    _ => past_tx.amount,
+                };
+                // this may lead to negative available_funds, unless the policy forbids it
+                let new_available = self.available_funds - disputed_amount;
+                if policy.forbid_overdrawing_disputes && new_available < Money::ZERO {
+                    return Err(Error::DisputeWouldOverdraw(tx.id()));
+                }
                 past_tx.disputed = true;
+                past_tx.held = disputed_amount;
                 self.available_funds = new_available;
-                self.held_funds += past_tx.amount;
+                self.held_funds += disputed_amount;
+                if !self.locked && policy.max_held.is_some_and(|max| self.held_funds > max) {
+                    self.locked = true;
+                    self.auto_locked_by = Some(tx.id());
+                }
             }
             Resolve => {
-                let past_tx = if let Some(past) = tx_history.past_transaction(tx.id()) {
+                let past_tx = if let Some(past) = ledger
+                    .get_mut(tx.id())
+                    .filter(|past| past.client == self.client)
+                {
                     past
                 } else {
                     return Err(Error::UnknownTxReference(tx.id()));
                 };
+                if past_tx.kind != CompletedTxKind::Deposit {
+                    return Err(Error::IndisputableTxReferenced(tx.id()));
+                }
                 if !past_tx.disputed {
                     return Err(Error::CantResolveIndisputedTx(tx.id()));
                 }
                 past_tx.disputed = false;
-                self.held_funds -= past_tx.amount;
-                self.available_funds += past_tx.amount;
+                past_tx.resolved_once = true;
+                self.held_funds -= past_tx.held;
+                self.available_funds += past_tx.held;
+                past_tx.held = Money::ZERO;
             }
             Chargeback => {
-                let past_tx = if let Some(past) = tx_history.past_transaction(tx.id()) {
+                let past_tx = if let Some(past) = ledger
+                    .get_mut(tx.id())
+                    .filter(|past| past.client == self.client)
+                {
                     past
                 } else {
                     return Err(Error::UnknownTxReference(tx.id()));
                 };
+                if past_tx.kind != CompletedTxKind::Deposit {
+                    return Err(Error::IndisputableTxReferenced(tx.id()));
+                }
                 if !past_tx.disputed {
                     return Err(Error::CantChargebackIndisputedTx(tx.id()));
                 }
-                self.held_funds -= past_tx.amount;
+                // only reachable through an inconsistent history, but never drive held negative
+                if past_tx.held > self.held_funds {
+                    return Err(Error::ChargebackExceedsHeld(tx.id()));
+                }
+                self.held_funds -= past_tx.held;
                 self.locked = true;
-                // unwrap won't panic because we already know this entry exists.
 
-                // zeroing the deposit's amount prevents repeat chargebacks
-                past_tx.amount = Money::ZERO;
+                // the chargeback settles the dispute, and deducting the charged back
+                // amount from the deposit prevents repeat chargebacks of the same funds,
+                // while any undisputed remainder may still be disputed later
+                past_tx.disputed = false;
+                past_tx.amount -= past_tx.held;
+                past_tx.held = Money::ZERO;
+                past_tx.charged_back = past_tx.amount == Money::ZERO;
             }
         }
         Ok(())
     }
+
+    /// The result of a deposit, withdrawal, or adjustment whose id is already recorded, under
+    /// [`Policy::idempotent_duplicates`]: success if it's the recorded transaction again,
+    /// or else a duplicate. `None` if the id isn't recorded, or `tx` is any other action.
+    ///
+    /// A deposit charged back has lost the amount charged back, so is no longer matched.
+    fn replayed(&self, tx: &Transaction, ledger: &mut dyn Ledger) -> Option<Result<(), Error>> {
+        use transaction::Action::*;
+        let (kind, amount) = match tx.action() {
+            Deposit { amount } => (CompletedTxKind::Deposit, Some(amount)),
+            Withdrawal { amount } => (CompletedTxKind::Withdrawal, Some(amount)),
+            // how much was withdrawn depended on the funds then available
+            WithdrawAll => (CompletedTxKind::Withdrawal, None),
+            Adjust { amount } => (CompletedTxKind::Adjustment, Some(amount)),
+            _ => return None,
+        };
+        let recorded = ledger.get_mut(tx.id())?;
+        let same = recorded.client == self.client
+            && recorded.kind == kind
+            && amount.is_none_or(|amount| amount == recorded.amount);
+        Some(if same {
+            Ok(())
+        } else {
+            Err(Error::DuplicateTransaction(tx.id()))
+        })
+    }
 }
 
 impl Serialize for Account {
@@ -129,7 +337,7 @@ impl Serialize for Account {
     where
         S: serde::Serializer,
     {
-        let mut state = serializer.serialize_struct("Color", 3)?;
+        let mut state = serializer.serialize_struct("Account", 5)?;
         state.serialize_field("client", &self.client)?;
         state.serialize_field("available", &self.available_funds)?;
         state.serialize_field("held", &self.held_funds)?;
@@ -139,6 +347,44 @@ impl Serialize for Account {
     }
 }
 
+// Deserializes a row of a previously written report, so that it may be merged into.
+impl<'de> Deserialize<'de> for Account {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct ReportAccount {
+            client: Client,
+            available: Money,
+            held: Money,
+            total: Money,
+            locked: bool,
+            // only reported when closing is allowed
+            #[serde(default)]
+            closed: bool,
+        }
+
+        let ReportAccount {
+            client,
+            available,
+            held,
+            total,
+            locked,
+            closed,
+        } = ReportAccount::deserialize(deserializer)?;
+        let account = Account::restore(client, available, held, locked, closed);
+        // total is redundant, but a mismatch means the report has been tampered with
+        if account.total() != total {
+            return Err(de::Error::custom(format!(
+                "client {}'s total {} is not the sum of available and held funds",
+                client, total
+            )));
+        }
+        Ok(account)
+    }
+}
+
 /// An error that occured while processing a transaction
 #[derive(Clone, Copy, Debug, PartialEq, Eq)] //, thiserror::Error)]
 pub enum Error {
@@ -158,4 +404,208 @@ pub enum Error {
     CantChargebackIndisputedTx(TxId),
     // #[error("Dispute attempted on transaction {0} that is already in dispute")]
     DuplicateDispute(TxId),
+    // #[error("Transaction {0}'s amount exceeds the maximum")]
+    AmountTooLarge(TxId),
+    // #[error("Chargeback {0} exceeds the account's held funds")]
+    ChargebackExceedsHeld(TxId),
+    // #[error("Partial dispute {0} exceeds the disputed transaction's amount")]
+    DisputeExceedsAmount(TxId),
+    // #[error("Dispute attempted on transaction {0} that was entirely charged back")]
+    AlreadyChargedBack(TxId),
+    // #[error("Dispute attempted on transaction {0} that was already disputed and resolved")]
+    AlreadyResolvedOnce(TxId),
+    // #[error("Withdrawal {0} would leave less than the minimum balance")]
+    BelowMinimumBalance(TxId),
+    // #[error("Adjustment {0} attempted without the policy allowing adjustments")]
+    AdjustmentsForbidden(TxId),
+    // #[error("Adjustment {0} would overflow the available funds")]
+    AdjustmentOverflows(TxId),
+    // #[error("Disputing adjustments is unsupported. tx: {0}")]
+    AdjustmentsAreIndisputable(TxId),
+    // #[error("Transaction {0}'s type has no registered handler")]
+    UnhandledAction(TxId),
+    // #[error("Resolve or chargeback referenced transaction {0}, which isn't a deposit")]
+    IndisputableTxReferenced(TxId),
+    // #[error("Dispute {0} would hold more than the limit on held funds across every account")]
+    GlobalHeldLimitExceeded(TxId),
+    // #[error("Dispute {0} would leave negative available funds")]
+    DisputeWouldOverdraw(TxId),
+    // #[error("Transaction {0} attempted on a closed account")]
+    AccountClosed(TxId),
+    // #[error("Close {0} attempted without the policy allowing accounts to be closed")]
+    ClosingForbidden(TxId),
+    // #[error("Close {0} attempted on an account with funds available or held")]
+    CloseOfNonEmptyAccount(TxId),
+    // #[error("Transaction {0}'s amount is negative")]
+    NegativeAmount(TxId),
+    // #[error("Withdrawal {0}'s amount rounds to zero")]
+    WithdrawalRoundsToZero(TxId),
 }
+
+impl Error {
+    /// The variant's name in snake case, such as `"duplicate_transaction"`,
+    /// for counting rejections by their reason
+    pub fn name(&self) -> &'static str {
+        use Error::*;
+        match self {
+            DuplicateTransaction(_) => "duplicate_transaction",
+            AccountLockedFundsFrozen(_) => "account_locked_funds_frozen",
+            InsufficientFundsForWithdrawal(_) => "insufficient_funds_for_withdrawal",
+            UnknownTxReference(_) => "unknown_tx_reference",
+            WithdrawalsAreIndisputable(_) => "withdrawals_are_indisputable",
+            CantResolveIndisputedTx(_) => "cant_resolve_indisputed_tx",
+            CantChargebackIndisputedTx(_) => "cant_chargeback_indisputed_tx",
+            DuplicateDispute(_) => "duplicate_dispute",
+            AmountTooLarge(_) => "amount_too_large",
+            ChargebackExceedsHeld(_) => "chargeback_exceeds_held",
+            DisputeExceedsAmount(_) => "dispute_exceeds_amount",
+            AlreadyChargedBack(_) => "already_charged_back",
+            AlreadyResolvedOnce(_) => "already_resolved_once",
+            BelowMinimumBalance(_) => "below_minimum_balance",
+            AdjustmentsForbidden(_) => "adjustments_forbidden",
+            AdjustmentOverflows(_) => "adjustment_overflows",
+            AdjustmentsAreIndisputable(_) => "adjustments_are_indisputable",
+            UnhandledAction(_) => "unhandled_action",
+            IndisputableTxReferenced(_) => "indisputable_tx_referenced",
+            GlobalHeldLimitExceeded(_) => "global_held_limit_exceeded",
+            DisputeWouldOverdraw(_) => "dispute_would_overdraw",
+            AccountClosed(_) => "account_closed",
+            ClosingForbidden(_) => "closing_forbidden",
+            CloseOfNonEmptyAccount(_) => "close_of_non_empty_account",
+            NegativeAmount(_) => "negative_amount",
+            WithdrawalRoundsToZero(_) => "withdrawal_rounds_to_zero",
+        }
+    }
+
+    /// The id of the rejected transaction
+    pub fn tx(&self) -> TxId {
+        use Error::*;
+        match *self {
+            DuplicateTransaction(tx)
+            | AccountLockedFundsFrozen(tx)
+            | InsufficientFundsForWithdrawal(tx)
+            | UnknownTxReference(tx)
+            | WithdrawalsAreIndisputable(tx)
+            | CantResolveIndisputedTx(tx)
+            | CantChargebackIndisputedTx(tx)
+            | DuplicateDispute(tx)
+            | AmountTooLarge(tx)
+            | ChargebackExceedsHeld(tx)
+            | DisputeExceedsAmount(tx)
+            | AlreadyChargedBack(tx)
+            | AlreadyResolvedOnce(tx)
+            | BelowMinimumBalance(tx)
+            | AdjustmentsForbidden(tx)
+            | AdjustmentOverflows(tx)
+            | AdjustmentsAreIndisputable(tx)
+            | UnhandledAction(tx)
+            | IndisputableTxReferenced(tx)
+            | GlobalHeldLimitExceeded(tx)
+            | DisputeWouldOverdraw(tx)
+            | AccountClosed(tx)
+            | ClosingForbidden(tx)
+            | CloseOfNonEmptyAccount(tx)
+            | NegativeAmount(tx)
+            | WithdrawalRoundsToZero(tx) => tx,
+        }
+    }
+}
+
+// hand-written in lieu of thiserror, with the messages noted on each variant
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Error::*;
+        match self {
+            DuplicateTransaction(tx) => write!(f, "Transaction already exists with id {}", tx),
+            AccountLockedFundsFrozen(tx) => write!(
+                f,
+                "Transaction {} attempted to modify funds in locked account",
+                tx
+            ),
+            InsufficientFundsForWithdrawal(tx) => {
+                write!(f, "Insufficient funds for withdrawal in tx {}", tx)
+            }
+            UnknownTxReference(tx) => write!(f, "Unknown transaction {} referenced", tx),
+            WithdrawalsAreIndisputable(tx) => {
+                write!(f, "Disputing withdrawals is unsupported. tx: {}", tx)
+            }
+            CantResolveIndisputedTx(tx) => {
+                write!(f, "Resolve attempted on indisupted transaction {}", tx)
+            }
+            CantChargebackIndisputedTx(tx) => {
+                write!(f, "Chargeback attempted on indisupted transaction {}", tx)
+            }
+            DuplicateDispute(tx) => write!(
+                f,
+                "Dispute attempted on transaction {} that is already in dispute",
+                tx
+            ),
+            AmountTooLarge(tx) => write!(f, "Transaction {}'s amount exceeds the maximum", tx),
+            ChargebackExceedsHeld(tx) => {
+                write!(f, "Chargeback {} exceeds the account's held funds", tx)
+            }
+            DisputeExceedsAmount(tx) => write!(
+                f,
+                "Partial dispute {} exceeds the disputed transaction's amount",
+                tx
+            ),
+            AlreadyChargedBack(tx) => write!(
+                f,
+                "Dispute attempted on transaction {} that was entirely charged back",
+                tx
+            ),
+            AlreadyResolvedOnce(tx) => write!(
+                f,
+                "Dispute attempted on transaction {} that was already disputed and resolved",
+                tx
+            ),
+            BelowMinimumBalance(tx) => write!(
+                f,
+                "Withdrawal {} would leave less than the minimum balance",
+                tx
+            ),
+            AdjustmentsForbidden(tx) => write!(
+                f,
+                "Adjustment {} attempted without the policy allowing adjustments",
+                tx
+            ),
+            AdjustmentOverflows(tx) => {
+                write!(f, "Adjustment {} would overflow the available funds", tx)
+            }
+            AdjustmentsAreIndisputable(tx) => {
+                write!(f, "Disputing adjustments is unsupported. tx: {}", tx)
+            }
+            UnhandledAction(tx) => {
+                write!(f, "Transaction {}'s type has no registered handler", tx)
+            }
+            IndisputableTxReferenced(tx) => write!(
+                f,
+                "Resolve or chargeback referenced transaction {}, which isn't a deposit",
+                tx
+            ),
+            GlobalHeldLimitExceeded(tx) => write!(
+                f,
+                "Dispute {} would hold more than the limit on held funds across every account",
+                tx
+            ),
+            DisputeWouldOverdraw(tx) => {
+                write!(f, "Dispute {} would leave negative available funds", tx)
+            }
+            AccountClosed(tx) => write!(f, "Transaction {} attempted on a closed account", tx),
+            ClosingForbidden(tx) => write!(
+                f,
+                "Close {} attempted without the policy allowing accounts to be closed",
+                tx
+            ),
+            CloseOfNonEmptyAccount(tx) => write!(
+                f,
+                "Close {} attempted on an account with funds available or held",
+                tx
+            ),
+            NegativeAmount(tx) => write!(f, "Transaction {}'s amount is negative", tx),
+            WithdrawalRoundsToZero(tx) => write!(f, "Withdrawal {}'s amount rounds to zero", tx),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
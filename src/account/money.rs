@@ -1,7 +1,16 @@
-use std::{
+//! `Money` and its arithmatic, which only depend on `core` and `alloc`
+//! so that they may be used without `std`.
+//!
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+use core::{
     fmt::{Debug, Display},
-    num::ParseIntError,
+    iter::Sum,
+    num::{IntErrorKind, ParseIntError},
     ops::{Add, AddAssign, Sub, SubAssign},
+    str::FromStr,
 };
 
 use serde::{de::Visitor, Deserialize, Serialize};
@@ -11,11 +20,18 @@ type MoneyInner = i64;
 const ONE_MONEY: MoneyInner = 1_0000;
 
 /// `Money` is a numeric quantity with four decimal places.
-#[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+///
+/// Parsing, whether through serde or [`FromStr`], doesn't tolerate any surrounding whitespace,
+/// so `" 1.5"` is rejected unless the CSV reader has trimmed it first.
+#[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Money(MoneyInner);
 
 impl Money {
     pub const ZERO: Money = Money(0);
+    pub const MAX: Money = Money(MoneyInner::MAX);
+    pub const MIN: Money = Money(MoneyInner::MIN);
+    /// The number of decimal places `Money` is kept to
+    pub const SCALE: usize = 4;
 
     #[cfg(test)]
     pub fn from_i64(num: i64) -> Self {
@@ -31,6 +47,10 @@ impl Money {
         self.0 < 0
     }
 
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
     #[cfg(test)]
     pub fn set_sign_negative(&mut self, negative: bool) {
         self.0 = self.0.abs();
@@ -39,9 +59,95 @@ impl Money {
         }
     }
 
+    /// Adds, returning `None` rather than panicking on overflow.
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Money)
+    }
+
+    /// Multiplies by `factor`, such as an exchange rate, rounding the product half away from
+    /// zero to [`Money::SCALE`] decimal places, or `None` if it's too large to be represented.
+    pub fn checked_mul(self, factor: Money) -> Option<Self> {
+        let product = i128::from(self.0) * i128::from(factor.0);
+        let one = i128::from(ONE_MONEY);
+        let (quotient, remainder) = (product / one, product % one);
+        let rounded = if remainder.abs() * 2 >= one {
+            quotient + product.signum()
+        } else {
+            quotient
+        };
+        MoneyInner::try_from(rounded).ok().map(Money)
+    }
+
+    /// Adds, clamping at [`Money::MAX`] or [`Money::MIN`] rather than panicking on overflow.
+    ///
+    /// Only suitable for display and aggregation, never for mutating a balance,
+    /// where a clamped result would silently corrupt the account.
+    pub fn saturating_add(self, rhs: Self) -> Self {
+        Money(self.0.saturating_add(rhs.0))
+    }
+
+    /// Subtracts, clamping at [`Money::MAX`] or [`Money::MIN`] rather than panicking on overflow.
+    ///
+    /// Only suitable for display and aggregation, never for mutating a balance,
+    /// where a clamped result would silently corrupt the account.
+    pub fn saturating_sub(self, rhs: Self) -> Self {
+        Money(self.0.saturating_sub(rhs.0))
+    }
+
+    /// Splits the amount into `n` equal shares, returning `(share, remainder)` such that
+    /// `n` shares plus the remainder are exactly the amount, or `None` if `n` is zero.
+    ///
+    /// The remainder is the ten-thousandths left over, with the amount's sign.
+    pub fn checked_div(self, n: u32) -> Option<(Money, Money)> {
+        let n = MoneyInner::from(n);
+        Some((Money(self.0.checked_div(n)?), Money(self.0.checked_rem(n)?)))
+    }
+
+    /// Formats with every decimal place, as accounting reports do, with negative amounts
+    /// in parentheses rather than signed, as in `(1.2300)` for -1.23.
+    pub fn format_accounting(self) -> String {
+        let fixed = format!("{:.*}", Money::SCALE, self);
+        match fixed.strip_prefix('-') {
+            Some(magnitude) => format!("({})", magnitude),
+            None => fixed,
+        }
+    }
+
+    /// Formats with as few decimal places as the amount needs, but at least one, as in `1.5`
+    /// for 1.5000 and `1.0` for 1.0000, the opposite of [`Money::format_accounting`].
+    pub fn to_compact_string(self) -> String {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        let one = ONE_MONEY as u64;
+        let fraction = format!("{:04}", abs % one);
+        let fraction = match fraction.trim_end_matches('0') {
+            "" => "0",
+            trimmed => trimmed,
+        };
+        format!("{}{}.{}", sign, abs / one, fraction)
+    }
+
+    /// The nearest `f64`, which for most amounts isn't exact,
+    /// and for those beyond 2^53 ten-thousandths, loses whole units too.
     pub fn to_f64(self) -> f64 {
         self.0 as f64 / ONE_MONEY as f64
     }
+
+    /// The `f64` of exactly this amount, or `None` if there isn't one.
+    ///
+    /// Only multiples of a sixteenth (ten thousandths divisible by 625) with
+    /// at most 53 significant bits are exactly representable, so e.g. `0.1` is `None`.
+    pub fn try_to_f64_lossless(self) -> Option<f64> {
+        const SIXTEENTH: MoneyInner = ONE_MONEY / 16;
+        let sixteenths = self.0 / SIXTEENTH;
+        // trailing zero bits only scale the exponent, so don't count against the mantissa
+        let significant = sixteenths
+            .unsigned_abs()
+            .checked_shr(sixteenths.trailing_zeros())
+            .unwrap_or(0);
+        (self.0 % SIXTEENTH == 0 && significant >> f64::MANTISSA_DIGITS == 0)
+            .then(|| sixteenths as f64 / 16.0)
+    }
 }
 
 impl Serialize for Money {
@@ -59,18 +165,10 @@ impl<'de> Deserialize<'de> for Money {
         D: serde::Deserializer<'de>,
     {
         struct MoneyVisitor;
-        impl MoneyVisitor {
-            fn parseint_error<E>(e: ParseIntError) -> E
-            where
-                E: serde::de::Error,
-            {
-                E::custom(format!("error parsing as integer: {}", e))
-            }
-        }
         impl<'de> Visitor<'de> for MoneyVisitor {
             type Value = Money;
 
-            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
                 formatter.write_str("a positive amount of money")
             }
 
@@ -78,61 +176,187 @@ impl<'de> Deserialize<'de> for Money {
             where
                 E: serde::de::Error,
             {
-                let (whole, fraction) = if let Some((whole, fraction_s)) = v.split_once('.') {
-                    // fraction can't start with negative sign
-                    if fraction_s.starts_with('-') {
-                        return Err(E::custom(format!(
-                            "invalid digit after decimal point in money field: {:?}",
-                            v
-                        )));
-                    }
-                    let mut fraction = if fraction_s.is_empty() {
-                        // "" is a valid fractional part
-                        0
-                    } else {
-                        fraction_s
-                            .parse::<MoneyInner>()
-                            .map_err(Self::parseint_error)?
-                    };
-                    fraction *= ONE_MONEY;
-                    // divide the fraction by 10 for every digit present after
-                    for _ in 0..fraction_s.len() {
-                        fraction += 5; // round up
-                        fraction /= 10;
-                    }
-                    // transfer sign from whole to fraction, keeping in mind that the
-                    // whole portion may be -0, so can't trust whole.parse to preserve sign
-                    if whole.starts_with('-') {
-                        fraction = -fraction;
-                    }
-                    // "-" isn't a valid integer, but it is a valid whole portion of a decimal,
-                    // but only if we have a fraction
-                    let whole = if (whole == "-" || whole.is_empty()) && !fraction_s.is_empty() {
-                        0
-                    } else {
-                        whole.parse::<MoneyInner>().map_err(Self::parseint_error)?
-                    };
-                    (whole, fraction)
-                } else {
-                    let whole = v.parse::<MoneyInner>().map_err(Self::parseint_error)?;
-                    let fraction = 0;
-                    (whole, fraction)
-                };
-                Ok(Money(whole * ONE_MONEY + fraction))
+                v.parse()
+                    .map_err(|e| E::custom(format!("{} in money field: {:?}", e, v)))
             }
         }
         deserializer.deserialize_str(MoneyVisitor)
     }
 }
 
+/// Why a string isn't an amount of [`Money`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoneyParseError {
+    /// there are no digits, as in `""` or `"."`
+    Empty,
+    /// a character other than a digit, sign, or decimal point, as in `"1x"`
+    InvalidDigit,
+    /// there's more than one decimal point, as in `"1.2.3"`
+    MultipleDecimalPoints,
+    /// the amount is too large to be represented
+    Overflow,
+    /// a sign anywhere but the start, as in `"1.-5"`
+    InvalidSign,
+}
+
+impl Display for MoneyParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            MoneyParseError::Empty => "no digits",
+            MoneyParseError::InvalidDigit => "invalid digit",
+            MoneyParseError::MultipleDecimalPoints => "more than one decimal point",
+            MoneyParseError::Overflow => "amount too large",
+            MoneyParseError::InvalidSign => "misplaced sign",
+        })
+    }
+}
+
+impl core::error::Error for MoneyParseError {}
+
+impl From<ParseIntError> for MoneyParseError {
+    fn from(e: ParseIntError) -> Self {
+        match e.kind() {
+            IntErrorKind::Empty => MoneyParseError::Empty,
+            IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => MoneyParseError::Overflow,
+            _ => MoneyParseError::InvalidDigit,
+        }
+    }
+}
+
+// the format read by the deserializer, also for amounts outside of serde (e.g. arguments).
+// Digits beyond the fourth decimal place are rounded away.
+impl FromStr for Money {
+    type Err = MoneyParseError;
+
+    fn from_str(v: &str) -> Result<Self, Self::Err> {
+        Money::parse_with_decimal_separator(v, '.')
+    }
+}
+
+impl Money {
+    /// Parses `v` like [`str::parse`], but with `separator` in place of the decimal point,
+    /// such as `','` for `"1,5"`. A `'.'` is then an invalid digit, like any other character.
+    pub fn parse_with_decimal_separator(v: &str, separator: char) -> Result<Self, MoneyParseError> {
+        Money::parse(v, separator, Money::SCALE)
+    }
+
+    /// Parses `v` like [`str::parse`], but rounding to `scale` decimal places rather than
+    /// [`Money::SCALE`], such as 0 for yen or 3 for dinars. Larger scales are clamped to it.
+    pub fn parse_at_scale(v: &str, scale: usize) -> Result<Self, MoneyParseError> {
+        Money::parse(v, '.', scale.min(Money::SCALE))
+    }
+
+    /// The amount rounded half away from zero to `scale` decimal places, such as to compare
+    /// amounts of a currency with fewer decimal places than [`Money::SCALE`],
+    /// or `None` if rounding away from zero passes [`Money::MAX`] or [`Money::MIN`].
+    pub fn round_to_scale(self, scale: usize) -> Option<Self> {
+        let Some(places) = Money::SCALE.checked_sub(scale).filter(|&places| places > 0) else {
+            return Some(self);
+        };
+        let divisor = (10 as MoneyInner).pow(places as u32);
+        let remainder = self.0 % divisor;
+        // towards zero, so can't overflow
+        let truncated = self.0 - remainder;
+        if remainder.abs() * 2 < divisor {
+            Some(Money(truncated))
+        } else if remainder > 0 {
+            truncated.checked_add(divisor).map(Money)
+        } else {
+            truncated.checked_sub(divisor).map(Money)
+        }
+    }
+
+    /// Parses `v` with `separator` as its decimal point, rounding to `scale` decimal places,
+    /// which mustn't exceed [`Money::SCALE`]
+    pub(crate) fn parse(v: &str, separator: char, scale: usize) -> Result<Self, MoneyParseError> {
+        // only the whole amount is signed, so in particular the fraction can't have a sign of its own
+        if v.char_indices()
+            .any(|(i, c)| i > 0 && matches!(c, '-' | '+'))
+        {
+            return Err(MoneyParseError::InvalidSign);
+        }
+        let (whole, fraction) = if let Some((whole, fraction_s)) = v.split_once(separator) {
+            if fraction_s.contains(separator) {
+                return Err(MoneyParseError::MultipleDecimalPoints);
+            }
+            // "" is a valid fractional part
+            if !fraction_s.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(MoneyParseError::InvalidDigit);
+            }
+            // only the digits within the scale count, rounded half up by the digit after them,
+            // so that however many digits there are, they can't overflow
+            let mut digits = fraction_s.bytes().map(|b| MoneyInner::from(b - b'0'));
+            let mut fraction = 0;
+            for _ in 0..scale {
+                fraction = fraction * 10 + digits.next().unwrap_or(0);
+            }
+            if digits.next().is_some_and(|digit| digit >= 5) {
+                fraction += 1;
+            }
+            fraction *= (10 as MoneyInner).pow((Money::SCALE - scale) as u32);
+            // transfer sign from whole to fraction, keeping in mind that the
+            // whole portion may be -0, so can't trust whole.parse to preserve sign
+            if whole.starts_with('-') {
+                fraction = -fraction;
+            }
+            // "-" and "+" aren't valid integers, but are valid whole portions of a decimal,
+            // but only if we have a fraction
+            let whole = if matches!(whole, "-" | "+" | "") && !fraction_s.is_empty() {
+                0
+            } else {
+                whole.parse::<MoneyInner>()?
+            };
+            (whole, fraction)
+        } else {
+            let whole = v.parse::<MoneyInner>()?;
+            let fraction = 0;
+            (whole, fraction)
+        };
+        whole
+            .checked_mul(ONE_MONEY)
+            .and_then(|whole| whole.checked_add(fraction))
+            .map(Money)
+            .ok_or(MoneyParseError::Overflow)
+    }
+}
+
+// Without a precision, all `Money::SCALE` fractional digits are written, so 1.5 is "1.5000".
+// With one, exactly that many are written, rounding half up when below `Money::SCALE`.
+// `Money::to_compact_string` writes as few as are needed instead.
 impl Display for Money {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}.{}", self.0 / ONE_MONEY, self.0.abs() % ONE_MONEY)
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        let one = ONE_MONEY as u64;
+        match f.precision().unwrap_or(Money::SCALE) {
+            precision if precision < Money::SCALE => {
+                let divisor = 10u64.pow((Money::SCALE - precision) as u32);
+                let rounded = (abs + divisor / 2) / divisor;
+                let one = one / divisor;
+                if precision == 0 {
+                    write!(f, "{}{}", sign, rounded)
+                } else {
+                    let (whole, fraction) = (rounded / one, rounded % one);
+                    write!(
+                        f,
+                        "{}{}.{:0width$}",
+                        sign,
+                        whole,
+                        fraction,
+                        width = precision
+                    )
+                }
+            }
+            precision => {
+                let padding = precision - Money::SCALE;
+                write!(f, "{}{}.{:04}{:0<padding$}", sign, abs / one, abs % one, "")
+            }
+        }
     }
 }
 
 impl Debug for Money {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_tuple("Money").field(&self.to_string()).finish()
     }
 }
@@ -163,6 +387,41 @@ impl SubAssign for Money {
         *self = *self - rhs;
     }
 }
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+impl<'a> Sum<&'a Money> for Money {
+    fn sum<I: Iterator<Item = &'a Money>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+// for interop with `rust_decimal` based systems, without a round trip through strings
+#[cfg(feature = "rust_decimal")]
+impl Money {
+    /// The amount as a `Decimal`, exactly, at [`Money::SCALE`] decimal places
+    pub fn to_decimal(self) -> rust_decimal::Decimal {
+        rust_decimal::Decimal::new(self.0, Money::SCALE as u32)
+    }
+
+    /// The `decimal` rounded half away from zero to [`Money::SCALE`] decimal places, as parsing
+    /// rounds, or [`MoneyParseError::Overflow`] if it's too large to be represented.
+    pub fn from_decimal(decimal: rust_decimal::Decimal) -> Result<Self, MoneyParseError> {
+        let mut rounded = decimal.round_dp_with_strategy(
+            Money::SCALE as u32,
+            rust_decimal::RoundingStrategy::MidpointAwayFromZero,
+        );
+        // fails to reach the scale only when there are too many whole digits
+        rounded.rescale(Money::SCALE as u32);
+        Some(rounded)
+            .filter(|rounded| rounded.scale() == Money::SCALE as u32)
+            .and_then(|rounded| MoneyInner::try_from(rounded.mantissa()).ok())
+            .map(Money)
+            .ok_or(MoneyParseError::Overflow)
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -283,4 +542,295 @@ mod tests {
         assert!(deser_str("-.0.").is_err());
         Ok(())
     }
+
+    #[test]
+    fn multiple_decimal_points_rejected_clearly() {
+        for s in ["1.2.3", "..5", "5..", "1..2", ".0."] {
+            assert_eq!(
+                s.parse::<Money>(),
+                Err(MoneyParseError::MultipleDecimalPoints),
+                "parsing {:?}",
+                s
+            );
+        }
+        assert_eq!(
+            deser_str("1.2.3").unwrap_err().to_string(),
+            r#"more than one decimal point in money field: "1.2.3""#
+        );
+        assert_eq!(
+            deser_str("..5").unwrap_err().to_string(),
+            r#"more than one decimal point in money field: "..5""#
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        for (s, error) in [
+            ("", MoneyParseError::Empty),
+            (".", MoneyParseError::Empty),
+            ("1x", MoneyParseError::InvalidDigit),
+            ("1.5e3", MoneyParseError::InvalidDigit),
+            ("-", MoneyParseError::InvalidDigit),
+            ("1.2.3", MoneyParseError::MultipleDecimalPoints),
+            (".0.", MoneyParseError::MultipleDecimalPoints),
+            ("99999999999999999999", MoneyParseError::Overflow),
+            ("922337203685478", MoneyParseError::Overflow),
+            ("1.-5", MoneyParseError::InvalidSign),
+            ("1.+5", MoneyParseError::InvalidSign),
+            ("--5", MoneyParseError::InvalidSign),
+            ("5-", MoneyParseError::InvalidSign),
+        ] {
+            assert_eq!(s.parse::<Money>(), Err(error), "parsing {:?}", s);
+        }
+        // many digits after the point are rounded away, rather than overflowing
+        assert_eq!("1.99999999999999999".parse(), Ok(Money(2_0000)));
+        assert_eq!("+5".parse(), Ok(Money(5_0000)));
+    }
+
+    #[test]
+    fn leading_zeros_and_plus_signs() {
+        for (s, money) in [
+            ("007.50", Money(7_5000)),
+            ("007", Money(7_0000)),
+            ("-007.50", Money(-7_5000)),
+            ("+7.50", Money(7_5000)),
+            ("+007", Money(7_0000)),
+            ("+.5", Money(5000)),
+            ("-.5", Money(-5000)),
+        ] {
+            assert_eq!(s.parse(), Ok(money), "parsing {:?}", s);
+        }
+        for s in ["++7", "+-7", "-+7", "--7.5", "7+"] {
+            assert_eq!(
+                s.parse::<Money>(),
+                Err(MoneyParseError::InvalidSign),
+                "parsing {:?}",
+                s
+            );
+        }
+        assert_eq!("+".parse::<Money>(), Err(MoneyParseError::InvalidDigit));
+        assert_eq!("+.".parse::<Money>(), Err(MoneyParseError::InvalidDigit));
+    }
+
+    #[test]
+    fn long_fractions_round_to_scale() {
+        let parse = |v: &str| v.parse::<Money>();
+        assert_eq!(parse("0.00000000000000000001"), Ok(Money::ZERO));
+        // 30 digits
+        assert_eq!(parse("0.123449999999999999999999999999"), Ok(Money(1234)));
+        assert_eq!(parse("0.123450000000000000000000000000"), Ok(Money(1235)));
+        assert_eq!(
+            parse("-7.999950000000000000000000000001"),
+            Ok(Money(-8_0000))
+        );
+        // rounded once, by the fifth digit alone
+        assert_eq!(parse("0.00004999"), Ok(Money::ZERO));
+        assert_eq!(
+            parse("0.12345678901234567890123456789x"),
+            Err(MoneyParseError::InvalidDigit)
+        );
+    }
+
+    #[test]
+    fn parse_with_decimal_comma() {
+        let parse = |v| Money::parse_with_decimal_separator(v, ',');
+        assert_eq!(parse("1,2345"), Ok(Money(1_2345)));
+        assert_eq!(parse("-0,5"), Ok(Money(-5000)));
+        assert_eq!(parse("12"), Ok(Money(12_0000)));
+        assert_eq!(parse("1.5"), Err(MoneyParseError::InvalidDigit));
+        assert_eq!(parse("1,2,3"), Err(MoneyParseError::MultipleDecimalPoints));
+    }
+
+    #[test]
+    fn lossless_f64_conversion() {
+        assert_eq!(Money(1_5000).try_to_f64_lossless(), Some(1.5));
+        assert_eq!(Money(-625).try_to_f64_lossless(), Some(-0.0625));
+        assert_eq!(Money::ZERO.try_to_f64_lossless(), Some(0.0));
+        // a tenth has no exact binary representation
+        assert_eq!(Money(1000).try_to_f64_lossless(), None);
+        // nor do sixteenths beyond 53 bits
+        let huge = Money(((1 << 53) + 1) * (ONE_MONEY / 16));
+        assert_eq!(huge.try_to_f64_lossless(), None);
+        // but beyond 2^53 in magnitude, those of at most 53 significant bits are exact
+        let even = Money(((1 << 53) + 2) * (ONE_MONEY / 16));
+        assert_eq!(
+            even.try_to_f64_lossless(),
+            Some(((1u64 << 53) + 2) as f64 / 16.0)
+        );
+        let widest = Money(((1 << 53) - 1) * (ONE_MONEY / 16));
+        assert_eq!(
+            widest.try_to_f64_lossless(),
+            Some(((1u64 << 53) - 1) as f64 / 16.0)
+        );
+    }
+
+    #[test]
+    fn checked_mul_rounds_half_away_from_zero() {
+        assert_eq!(
+            Money(2_5000).checked_mul(Money(1_1000)),
+            Some(Money(2_7500))
+        );
+        // 0.0001 * 0.5 is half a ten-thousandth
+        assert_eq!(Money(1).checked_mul(Money(5000)), Some(Money(1)));
+        assert_eq!(Money(-1).checked_mul(Money(5000)), Some(Money(-1)));
+        assert_eq!(Money(1).checked_mul(Money(4999)), Some(Money::ZERO));
+        assert_eq!(Money::MAX.checked_mul(Money(2_0000)), None);
+    }
+
+    #[test]
+    fn checked_div_distributes_exactly() {
+        let one = Money(1_0000);
+        let (share, remainder) = one.checked_div(3).unwrap();
+        assert_eq!((share, remainder), (Money(3333), Money(1)));
+        assert_eq!(share + share + share + remainder, one);
+
+        let (share, remainder) = Money(-1_0000).checked_div(3).unwrap();
+        assert_eq!((share, remainder), (Money(-3333), Money(-1)));
+        assert_eq!(
+            Money(7_5000).checked_div(1),
+            Some((Money(7_5000), Money::ZERO))
+        );
+        assert_eq!(one.checked_div(0), None);
+    }
+
+    #[test]
+    fn accounting_format() {
+        assert_eq!(Money(1_2300).format_accounting(), "1.2300");
+        assert_eq!(Money::ZERO.format_accounting(), "0.0000");
+        assert_eq!(Money(-1_2300).format_accounting(), "(1.2300)");
+        assert_eq!(Money(-1).format_accounting(), "(0.0001)");
+        assert_eq!(Money::MIN.format_accounting(), "(922337203685477.5808)");
+    }
+
+    #[test]
+    fn display_is_fixed_scale() {
+        assert_eq!(Money(1_0500).to_string(), "1.0500");
+        assert_eq!(Money(1_5000).to_string(), "1.5000");
+        assert_eq!(Money(10_0000).to_string(), "10.0000");
+        assert_eq!(Money(1).to_string(), "0.0001");
+        assert_eq!(Money(-5000).to_string(), "-0.5000");
+        assert_eq!(Money::ZERO.to_string(), "0.0000");
+    }
+
+    #[test]
+    fn compact_string_trims_trailing_zeros() {
+        assert_eq!(Money(1_5000).to_compact_string(), "1.5");
+        assert_eq!(Money(1_0000).to_compact_string(), "1.0");
+        assert_eq!(Money(1_2345).to_compact_string(), "1.2345");
+        assert_eq!(Money(-1_2000).to_compact_string(), "-1.2");
+        assert_eq!(Money(1).to_compact_string(), "0.0001");
+        assert_eq!(Money::ZERO.to_compact_string(), "0.0");
+        assert_eq!(Money::MIN.to_compact_string(), "-922337203685477.5808");
+    }
+
+    #[test]
+    fn compact_string_differs_from_fixed_display() {
+        assert_eq!(Money(1_5000).to_string(), "1.5000");
+        assert_eq!(Money(1_5000).to_compact_string(), "1.5");
+        assert_eq!(Money(-10_0000).to_string(), "-10.0000");
+        assert_eq!(Money(-10_0000).to_compact_string(), "-10.0");
+    }
+
+    #[test]
+    fn display_with_precision_is_fixed() {
+        assert_eq!(format!("{:.4}", Money(5000)), "0.5000");
+        assert_eq!(format!("{:.4}", Money(ONE_MONEY)), "1.0000");
+        assert_eq!(format!("{:.4}", Money(-1_0500)), "-1.0500");
+        assert_eq!(format!("{:.6}", Money(1_0500)), "1.050000");
+        assert_eq!(format!("{:.2}", Money(1_0050)), "1.01");
+        assert_eq!(format!("{:.2}", Money(-1_0049)), "-1.00");
+        assert_eq!(format!("{:.0}", Money(2_5000)), "3");
+    }
+
+    #[test]
+    fn display_round_trips() -> Result<(), SerdeError> {
+        for money in [
+            Money(1_0500),
+            Money(-5000),
+            Money(1),
+            Money::MAX,
+            Money::MIN,
+        ] {
+            assert_eq!(deser_str(&money.to_string())?, money);
+            assert_eq!(deser_str(&format!("{:.4}", money))?, money);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn is_zero() {
+        assert!(Money::ZERO.is_zero());
+        assert!(!Money(1).is_zero());
+        assert!(!Money(-1).is_zero());
+        assert!(!Money(-ONE_MONEY).is_zero());
+    }
+
+    #[test]
+    fn sum_of_amounts() -> Result<(), SerdeError> {
+        let amounts = [deser_str("1.5")?, deser_str("2.25")?, deser_str("-0.75")?];
+        assert_eq!(amounts.iter().sum::<Money>(), Money(3_0000));
+        assert_eq!(amounts.into_iter().sum::<Money>(), Money(3_0000));
+        assert_eq!(core::iter::empty::<Money>().sum::<Money>(), Money::ZERO);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn sum_panics_on_overflow() {
+        let _: Money = [Money::MAX, Money(1)].into_iter().sum();
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn decimal_round_trip() {
+        use rust_decimal::Decimal;
+        for money in [
+            Money(1_2345),
+            Money(-7_5000),
+            Money::ZERO,
+            Money::MAX,
+            Money::MIN,
+        ] {
+            assert_eq!(Money::from_decimal(money.to_decimal()), Ok(money));
+        }
+        assert_eq!(Money(1_2345).to_decimal(), Decimal::new(12345, 4));
+        // fewer decimal places than the scale are fine
+        assert_eq!(Money::from_decimal(Decimal::new(15, 1)), Ok(Money(1_5000)));
+        // more are rounded half away from zero, as parsing does
+        assert_eq!(
+            Money::from_decimal(Decimal::new(123456, 5)),
+            Ok(Money(1_2346))
+        );
+        assert_eq!(
+            Money::from_decimal(Decimal::new(123454, 5)),
+            Ok(Money(1_2345))
+        );
+        assert_eq!(
+            Money::from_decimal(Decimal::new(-123455, 5)),
+            Ok(Money(-1_2346))
+        );
+        assert_eq!(
+            Money::from_decimal(Decimal::MAX),
+            Err(MoneyParseError::Overflow)
+        );
+        assert_eq!(
+            Money::from_decimal(Money::MAX.to_decimal() + Decimal::ONE),
+            Err(MoneyParseError::Overflow)
+        );
+    }
+
+    #[test]
+    fn saturating_arithmatic_clamps() {
+        let one = Money(ONE_MONEY);
+        assert_eq!(Money::MAX.saturating_add(one), Money::MAX);
+        assert_eq!(Money::MIN.saturating_sub(one), Money::MIN);
+        assert_eq!(Money::MAX.saturating_sub(Money(-ONE_MONEY)), Money::MAX);
+        assert_eq!(Money::MIN.saturating_add(Money(-ONE_MONEY)), Money::MIN);
+        // and doesn't clamp when in bounds
+        assert_eq!(
+            Money::MAX.saturating_sub(one),
+            Money(MoneyInner::MAX - ONE_MONEY)
+        );
+        assert_eq!(one.saturating_add(one), Money(2 * ONE_MONEY));
+    }
 }
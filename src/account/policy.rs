@@ -0,0 +1,41 @@
+//! `Policy` configures the optional rules enforced by
+//! [`Account::process_transaction_with`](super::Account::process_transaction_with)
+//!
+use super::Money;
+
+/// The default policy enforces none of the optional rules.
+#[derive(Clone, Debug, Default)]
+pub struct Policy {
+    /// deposits and withdrawals of more than this amount are rejected
+    pub max_amount: Option<Money>,
+    /// disputes of a transaction that has already been disputed and resolved are rejected
+    pub forbid_redispute: bool,
+    /// deposits into locked accounts are accepted, only withdrawals are frozen
+    pub allow_deposits_when_locked: bool,
+    /// withdrawals must leave at least this much available, zero by default
+    pub minimum_balance: Money,
+    /// a dispute holding more than this in total locks the account, though it's still applied
+    pub max_held: Option<Money>,
+    /// adjustments of the available funds are applied, rather than rejected
+    pub allow_adjustments: bool,
+    /// deposits and withdrawals of zero are accepted, but not recorded,
+    /// so they can't be disputed and their ids remain free
+    pub skip_zero_amounts: bool,
+    /// a deposit, withdrawal, or adjustment with the id of one already recorded for the same
+    /// client, of the same kind and amount, is accepted without being applied again, such as
+    /// when replaying an input. Any other transaction with a recorded id is still a duplicate.
+    pub idempotent_duplicates: bool,
+    /// disputes that would leave the available funds negative, as when the disputed deposit has
+    /// since been withdrawn, are rejected rather than applied. Unrelated to `minimum_balance`,
+    /// which only withdrawals must leave.
+    pub forbid_overdrawing_disputes: bool,
+    /// empty accounts may be closed, after which every transaction for the client is rejected
+    pub allow_closing: bool,
+}
+
+impl Policy {
+    /// Is `amount` permitted for a single deposit or withdrawal?
+    pub(super) fn allows_amount(&self, amount: Money) -> bool {
+        self.max_amount.is_none_or(|max| amount <= max)
+    }
+}
@@ -0,0 +1,127 @@
+//! Reversing transactions already applied to an [`Account`], such as to reconstruct
+//! the state before a period for "what-if" analysis.
+//!
+use super::{Account, Action, CompletedTxKind, Ledger, Money, Transaction, TxHistory, TxId};
+use std::fmt::Display;
+
+/// Why a transaction couldn't be exactly reversed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Irreversible {
+    /// chargebacks forget how much of the deposit they charged back
+    Chargeback(TxId),
+    /// resolves forget how much of the transaction was held
+    Resolve(TxId),
+    /// locking forgets whether it was a chargeback or the held funds cap that locked the account
+    Locked(TxId),
+    /// only the custom action's handler knows what it did
+    Custom(TxId),
+    /// no transaction was recorded as applied to the account, as it's described
+    NotRecorded(TxId),
+    /// the transaction is still in dispute, so the dispute must be reversed first
+    StillDisputed(TxId),
+    /// the disputed transaction isn't in dispute any longer
+    NotDisputed(TxId),
+}
+
+impl Irreversible {
+    /// The id of the irreversible transaction
+    pub fn tx(&self) -> TxId {
+        use Irreversible::*;
+        match *self {
+            Chargeback(tx) | Resolve(tx) | Locked(tx) | Custom(tx) | NotRecorded(tx)
+            | StillDisputed(tx) | NotDisputed(tx) => tx,
+        }
+    }
+}
+
+impl Display for Irreversible {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Irreversible::*;
+        match self {
+            Chargeback(tx) => write!(f, "Chargeback {} can't be reversed", tx),
+            Resolve(tx) => write!(f, "Resolve {} can't be reversed", tx),
+            Locked(tx) => write!(
+                f,
+                "Transaction {} can't be reversed in a locked account",
+                tx
+            ),
+            Custom(tx) => write!(f, "Custom transaction {} can't be reversed", tx),
+            NotRecorded(tx) => write!(f, "Transaction {} isn't recorded as applied", tx),
+            StillDisputed(tx) => write!(f, "Transaction {} is still in dispute", tx),
+            NotDisputed(tx) => write!(f, "Dispute {} is no longer in dispute", tx),
+        }
+    }
+}
+
+impl std::error::Error for Irreversible {}
+
+impl Account {
+    /// Undoes `tx`, the last transaction applied to the account and recorded in `history`,
+    /// leaving both as they were before it.
+    ///
+    /// Deposits, withdrawals, adjustments, disputes, and closes are reversed exactly, forgetting any
+    /// deposit, withdrawal, or adjustment reversed. Everything else is irreversible, as is
+    /// anything in a locked account.
+    pub fn reverse_transaction(
+        &mut self,
+        tx: &Transaction,
+        history: &mut TxHistory,
+    ) -> Result<(), Irreversible> {
+        let id = tx.id();
+        match tx.action() {
+            Action::Chargeback => return Err(Irreversible::Chargeback(id)),
+            Action::Resolve => return Err(Irreversible::Resolve(id)),
+            Action::Custom { .. } => return Err(Irreversible::Custom(id)),
+            _ if self.locked => return Err(Irreversible::Locked(id)),
+            Action::Deposit { .. }
+            | Action::Withdrawal { .. }
+            | Action::WithdrawAll
+            | Action::Adjust { .. } => {
+                let recorded = history
+                    .get(id)
+                    .filter(|recorded| recorded.client == self.client)
+                    .ok_or(Irreversible::NotRecorded(id))?;
+                if recorded.disputed {
+                    return Err(Irreversible::StillDisputed(id));
+                }
+                // the change to the available funds, undone
+                let credited = match (tx.action(), recorded.kind) {
+                    (Action::Deposit { amount }, CompletedTxKind::Deposit)
+                    | (Action::Adjust { amount }, CompletedTxKind::Adjustment)
+                        if amount == recorded.amount =>
+                    {
+                        amount
+                    }
+                    (Action::Withdrawal { amount }, CompletedTxKind::Withdrawal)
+                        if amount == recorded.amount =>
+                    {
+                        Money::ZERO - amount
+                    }
+                    // the amount withdrawn was recorded
+                    (Action::WithdrawAll, CompletedTxKind::Withdrawal) => {
+                        Money::ZERO - recorded.amount
+                    }
+                    _ => return Err(Irreversible::NotRecorded(id)),
+                };
+                self.available_funds -= credited;
+                history.remove(id);
+            }
+            Action::Dispute | Action::PartialDispute { .. } => {
+                let past_tx = history
+                    .get_mut(id)
+                    .filter(|past| past.client == self.client)
+                    .ok_or(Irreversible::NotRecorded(id))?;
+                if !past_tx.disputed {
+                    return Err(Irreversible::NotDisputed(id));
+                }
+                self.held_funds -= past_tx.held;
+                self.available_funds += past_tx.held;
+                past_tx.held = Money::ZERO;
+                past_tx.disputed = false;
+            }
+            // only empty accounts are closed, so reopening it is all there is to undo
+            Action::Close => self.closed = false,
+        }
+        Ok(())
+    }
+}
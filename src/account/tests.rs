@@ -10,7 +10,11 @@ fn process_tx_skips_dup_deposits() {
     let deposit_amount = Money::from_i64(123_0000);
     let client = 725;
     let tx_id = 101;
-    let tx = Transaction::new(Action::new_deposit(deposit_amount), client, tx_id);
+    let tx = Transaction::new(
+        Action::try_new_deposit(deposit_amount).unwrap(),
+        client,
+        tx_id,
+    );
     let mut account = Account::new(client);
     assert_eq!(Ok(()), account.process_transaction(&tx, &mut tx_history));
     for _ in 0..10 {
@@ -35,7 +39,11 @@ fn process_tx_denies_deposit_in_locked_account() {
     account.locked = true;
 
     // process a withdrawal
-    let withdrawal = Transaction::new(Action::new_deposit(deposit_amount), client, deposit_id);
+    let withdrawal = Transaction::new(
+        Action::try_new_deposit(deposit_amount).unwrap(),
+        client,
+        deposit_id,
+    );
     assert_eq!(
         Err(Error::AccountLockedFundsFrozen(deposit_id)),
         account.process_transaction(&withdrawal, &mut tx_history)
@@ -58,7 +66,7 @@ fn process_tx_denies_withdrawal_in_locked_account() {
 
     // process a withdrawal
     let withdrawal = Transaction::new(
-        Action::new_withdrawal(withdrawal_amount),
+        Action::try_new_withdrawal(withdrawal_amount).unwrap(),
         client,
         withdrawal_id,
     );
@@ -71,6 +79,37 @@ fn process_tx_denies_withdrawal_in_locked_account() {
     assert!(account.held_funds == Money::ZERO);
 }
 
+#[test]
+fn permissive_policy_allows_only_deposits_in_locked_account() {
+    let mut tx_history = tx_history::TxHistory::default();
+    let amount = Money::from_i64(123_0000);
+    let client = 725;
+    let mut account = Account::new(client);
+    let policy = Policy {
+        allow_deposits_when_locked: true,
+        ..Policy::default()
+    };
+
+    // Lock the account
+    account.locked = true;
+
+    let deposit = Transaction::new(Action::try_new_deposit(amount).unwrap(), client, 102);
+    assert_eq!(
+        Ok(()),
+        account.process_transaction_with(&deposit, &mut tx_history, &policy)
+    );
+    assert_eq!(account.available_funds, amount);
+
+    // withdrawals are still frozen, even with funds available
+    let withdrawal = Transaction::new(Action::try_new_withdrawal(amount).unwrap(), client, 103);
+    assert_eq!(
+        Err(Error::AccountLockedFundsFrozen(103)),
+        account.process_transaction_with(&withdrawal, &mut tx_history, &policy)
+    );
+    assert_eq!(account.available_funds, amount);
+    assert!(account.locked);
+}
+
 #[test]
 fn process_tx_allows_dispute_and_chargeback_in_locked_account() {
     let mut tx_history = tx_history::TxHistory::default();
@@ -80,7 +119,11 @@ fn process_tx_allows_dispute_and_chargeback_in_locked_account() {
     let mut account = Account::new(client);
 
     // process a deposit, that will be charged back soon
-    let deposit = Transaction::new(Action::new_deposit(deposit_amount), client, deposit_id);
+    let deposit = Transaction::new(
+        Action::try_new_deposit(deposit_amount).unwrap(),
+        client,
+        deposit_id,
+    );
     assert_eq!(
         Ok(()),
         account.process_transaction(&deposit, &mut tx_history)
@@ -116,7 +159,11 @@ fn process_tx_allows_dispute_and_resolve_in_locked_account() {
     let mut account = Account::new(client);
 
     // process a deposit, that will be charged back soon
-    let deposit = Transaction::new(Action::new_deposit(deposit_amount), client, deposit_id);
+    let deposit = Transaction::new(
+        Action::try_new_deposit(deposit_amount).unwrap(),
+        client,
+        deposit_id,
+    );
     assert_eq!(
         Ok(()),
         account.process_transaction(&deposit, &mut tx_history)
@@ -154,7 +201,11 @@ fn process_tx_skips_dup_withdrawals() {
     let mut account = Account::new(client);
 
     // process one deposit to put a balance in the account
-    let deposit = Transaction::new(Action::new_deposit(deposit_amount), client, deposit_id);
+    let deposit = Transaction::new(
+        Action::try_new_deposit(deposit_amount).unwrap(),
+        client,
+        deposit_id,
+    );
     assert_eq!(
         Ok(()),
         account.process_transaction(&deposit, &mut tx_history)
@@ -162,7 +213,7 @@ fn process_tx_skips_dup_withdrawals() {
 
     // process ten of the same withdrawal
     let withdrawal = Transaction::new(
-        Action::new_withdrawal(withdrawal_amount),
+        Action::try_new_withdrawal(withdrawal_amount).unwrap(),
         client,
         withdrawal_id,
     );
@@ -221,6 +272,39 @@ fn csv_with_negative_amounts_rejected() {
     assert!(parse_test_data(&data).is_err());
 }
 
+#[test]
+fn negative_amounts_rejected_by_fallible_constructors() {
+    let negative = Money::from_i64(-1_0000);
+    for action in [
+        Action::try_new_deposit(negative),
+        Action::try_new_withdrawal(negative),
+        Action::try_new_partial_dispute(negative),
+    ] {
+        assert_eq!(action, Err(ValidationError::NegativeAmount(negative)));
+    }
+    assert_eq!(
+        Action::try_new_deposit(Money::ZERO),
+        Ok(Action::Deposit {
+            amount: Money::ZERO
+        })
+    );
+}
+
+#[test]
+fn json_with_negative_amounts_rejected() {
+    for action_type in ["deposit", "withdrawal"] {
+        let json = format!(
+            r#"{{"type": "{}", "client": 1, "tx": 1, "amount": "-1.5"}}"#,
+            action_type
+        );
+        let error = serde_json::from_str::<Transaction>(&json).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid value: floating point `-1.5`, expected a positive amount of moneys"
+        );
+    }
+}
+
 #[test]
 fn deposits_without_amount_rejected() {
     let data = [
@@ -243,6 +327,27 @@ fn withdrawals_without_amount_rejected() {
     assert!(parse_test_data(&data).is_err());
 }
 
+fn deserialize_error_message(header: &[&str], record: &[&str]) -> String {
+    let header = csv::StringRecord::from(header.to_vec());
+    let record = csv::StringRecord::from(record.to_vec());
+    let error = record
+        .deserialize::<Transaction>(Some(&header))
+        .unwrap_err();
+    match error.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err.kind().to_string(),
+        _ => panic!("expected a deserialize error, got {}", error),
+    }
+}
+
+#[test]
+fn deposit_short_record_same_as_empty_amount() {
+    let header = ["type", "client", "tx", "amount"];
+    let short = deserialize_error_message(&header, &["deposit", "1", "1"]);
+    let empty = deserialize_error_message(&header, &["deposit", "1", "1", ""]);
+    assert_eq!(short, "missing field `amount`");
+    assert_eq!(short, empty);
+}
+
 #[test]
 fn dispute_without_amount_accepted() {
     let data = [
@@ -254,6 +359,29 @@ fn dispute_without_amount_accepted() {
     assert!(parse_test_data(&data).is_ok());
 }
 
+#[test]
+fn deposit_whitespace_amount_same_as_empty_amount() {
+    let header = ["type", "client", "tx", "amount"];
+    let spaces = deserialize_error_message(&header, &["deposit", "1", "1", "   "]);
+    let empty = deserialize_error_message(&header, &["deposit", "1", "1", ""]);
+    assert_eq!(spaces, "missing field `amount`");
+    assert_eq!(spaces, empty);
+}
+
+#[test]
+fn dispute_whitespace_amount_accepted() {
+    let data = [
+        ("amount", "   "),
+        ("tx", "100"),
+        ("type", "dispute"),
+        ("client", "615"),
+    ];
+    assert_eq!(
+        parse_test_data(&data).unwrap(),
+        Transaction::new(Action::Dispute, 615, 100)
+    );
+}
+
 #[test]
 fn dispute_with_amount_rejected() {
     let data = [
@@ -309,6 +437,142 @@ fn withdrawal_with_amount_accepted() {
     assert!(parse_test_data(&data).is_ok());
 }
 
+#[test]
+fn transaction_types_are_case_insensitive() {
+    let amount = Money::from_i64(100_0000);
+    for (action_type, amount_s, action) in [
+        ("Deposit", "100", Action::try_new_deposit(amount).unwrap()),
+        (
+            "WITHDRAWAL",
+            "100",
+            Action::try_new_withdrawal(amount).unwrap(),
+        ),
+        ("Dispute", "", Action::new_dispute()),
+        ("rEsOlVe", "", Action::new_resolve()),
+    ] {
+        let data = [
+            ("type", action_type),
+            ("client", "615"),
+            ("tx", "100"),
+            ("amount", amount_s),
+        ];
+        assert_eq!(
+            parse_test_data(&data).unwrap(),
+            Transaction::new(action, 615, 100)
+        );
+    }
+    let data = [
+        ("type", "deposits"),
+        ("client", "615"),
+        ("tx", "100"),
+        ("amount", "100"),
+    ];
+    assert!(parse_test_data(&data).is_err());
+}
+
+#[test]
+fn transactions_round_trip_through_csv() {
+    let amount = Money::from_i64(12_3400);
+    let txs = [
+        Transaction::new(Action::try_new_deposit(amount).unwrap(), 1, 1),
+        Transaction::new(Action::try_new_withdrawal(amount).unwrap(), 2, 2),
+        Transaction::new(Action::new_withdraw_all(), 2, 6),
+        Transaction::new(Action::new_dispute(), 3, 1),
+        Transaction::new(Action::new_resolve(), 4, 1),
+        Transaction::new(Action::new_chargeback(), 5, 1),
+    ];
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for tx in &txs {
+        writer.serialize(tx).unwrap();
+    }
+    let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert_eq!(
+        written,
+        "type,client,tx,amount\n\
+         deposit,1,1,12.3400\n\
+         withdrawal,2,2,12.3400\n\
+         withdraw_all,2,6,\n\
+         dispute,3,1,\n\
+         resolve,4,1,\n\
+         chargeback,5,1,\n"
+    );
+    let read: Vec<Transaction> = csv::Reader::from_reader(written.as_bytes())
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(read, txs);
+
+    // partial disputes have no record to write
+    let partial = Transaction::new(Action::try_new_partial_dispute(amount).unwrap(), 1, 1);
+    assert!(csv::Writer::from_writer(Vec::new())
+        .serialize(partial)
+        .is_err());
+}
+
+#[test]
+fn withdraw_all_empties_available() {
+    let mut tx_history = tx_history::TxHistory::default();
+    let client = 725;
+    let mut account = Account::new(client);
+    let deposit = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(12_3456)).unwrap(),
+        client,
+        1,
+    );
+    assert_eq!(
+        Ok(()),
+        account.process_transaction(&deposit, &mut tx_history)
+    );
+
+    let withdraw_all = Transaction::new(Action::new_withdraw_all(), client, 2);
+    assert_eq!(
+        Ok(()),
+        account.process_transaction(&withdraw_all, &mut tx_history)
+    );
+    assert_eq!(account.available_funds, Money::ZERO);
+    // recorded with the amount actually withdrawn
+    let recorded = tx_history.get_mut(2).unwrap();
+    assert_eq!(recorded.kind, tx_history::CompletedTxKind::Withdrawal);
+    assert_eq!(recorded.amount, Money::from_i64(12_3456));
+
+    // an empty account withdraws nothing, unless zero amounts are skipped
+    let withdraw_all = Transaction::new(Action::new_withdraw_all(), client, 3);
+    assert_eq!(
+        Ok(()),
+        account.process_transaction(&withdraw_all, &mut tx_history)
+    );
+    assert_eq!(tx_history.get_mut(3).unwrap().amount, Money::ZERO);
+    let policy = Policy {
+        skip_zero_amounts: true,
+        ..Policy::default()
+    };
+    let withdraw_all = Transaction::new(Action::new_withdraw_all(), client, 4);
+    assert_eq!(
+        Ok(()),
+        account.process_transaction_with(&withdraw_all, &mut tx_history, &policy)
+    );
+    assert!(!tx_history.contains(4));
+
+    // and a locked account withdraws nothing at all
+    let deposit = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(5_0000)).unwrap(),
+        client,
+        5,
+    );
+    assert_eq!(
+        Ok(()),
+        account.process_transaction(&deposit, &mut tx_history)
+    );
+    account.locked = true;
+    let withdraw_all = Transaction::new(Action::new_withdraw_all(), client, 6);
+    assert_eq!(
+        Err(Error::AccountLockedFundsFrozen(6)),
+        account.process_transaction(&withdraw_all, &mut tx_history)
+    );
+    assert_eq!(account.available_funds, Money::from_i64(5_0000));
+    assert!(!tx_history.contains(6));
+}
+
 #[test]
 fn account_total_simple_addition() {
     let client = 266;
@@ -337,7 +601,11 @@ fn duplicate_disputes_are_rejected() {
     let mut account = Account::new(client);
 
     // process one deposit for us to dispute
-    let deposit = Transaction::new(Action::new_deposit(deposit_amount), client, deposit_id);
+    let deposit = Transaction::new(
+        Action::try_new_deposit(deposit_amount).unwrap(),
+        client,
+        deposit_id,
+    );
     assert_eq!(
         Ok(()),
         account.process_transaction(&deposit, &mut tx_history)
@@ -377,7 +645,11 @@ fn chargebacks_dont_free_txid() {
     let mut account = Account::new(client);
 
     // deposit that we'll chargeback, freezing `client`'s account
-    let deposit = Transaction::new(Action::new_deposit(deposit_amount), client, deposit_id);
+    let deposit = Transaction::new(
+        Action::try_new_deposit(deposit_amount).unwrap(),
+        client,
+        deposit_id,
+    );
     assert_eq!(
         Ok(()),
         account.process_transaction(&deposit, &mut tx_history)
@@ -400,7 +672,7 @@ fn chargebacks_dont_free_txid() {
     let mut second_account = Account::new(second_client);
     let second_deposit_amount = Money::from_i64(444_0000);
     let second_deposit = Transaction::new(
-        Action::new_deposit(second_deposit_amount),
+        Action::try_new_deposit(second_deposit_amount).unwrap(),
         second_client,
         deposit_id,
     );
@@ -409,3 +681,989 @@ fn chargebacks_dont_free_txid() {
         second_account.process_transaction(&second_deposit, &mut tx_history)
     );
 }
+
+#[test]
+fn max_amount_policy_limits_deposits_and_withdrawals() {
+    let mut tx_history = tx_history::TxHistory::default();
+    let client = 725;
+    let mut account = Account::new(client);
+    let policy = Policy {
+        max_amount: Some(Money::from_i64(100_0000)),
+        ..Policy::default()
+    };
+
+    // below and at the maximum are accepted
+    for (id, amount) in [(1, 99_9999), (2, 100_0000)] {
+        let deposit = Transaction::new(
+            Action::try_new_deposit(Money::from_i64(amount)).unwrap(),
+            client,
+            id,
+        );
+        assert_eq!(
+            Ok(()),
+            account.process_transaction_with(&deposit, &mut tx_history, &policy)
+        );
+    }
+    let withdrawal = Transaction::new(
+        Action::try_new_withdrawal(Money::from_i64(100_0000)).unwrap(),
+        client,
+        3,
+    );
+    assert_eq!(
+        Ok(()),
+        account.process_transaction_with(&withdrawal, &mut tx_history, &policy)
+    );
+
+    // above the maximum is rejected, without being recorded
+    let deposit = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(100_0001)).unwrap(),
+        client,
+        4,
+    );
+    assert_eq!(
+        Err(Error::AmountTooLarge(4)),
+        account.process_transaction_with(&deposit, &mut tx_history, &policy)
+    );
+    let withdrawal = Transaction::new(
+        Action::try_new_withdrawal(Money::from_i64(100_0001)).unwrap(),
+        client,
+        5,
+    );
+    assert_eq!(
+        Err(Error::AmountTooLarge(5)),
+        account.process_transaction_with(&withdrawal, &mut tx_history, &policy)
+    );
+    assert!(tx_history.get_mut(4).is_none());
+    assert!(tx_history.get_mut(5).is_none());
+    assert_eq!(account.available_funds, Money::from_i64(99_9999));
+    assert_eq!(account.held_funds, Money::ZERO);
+}
+
+#[test]
+fn chargeback_exceeding_held_rejected() {
+    let mut tx_history = tx_history::TxHistory::default();
+    let deposit_amount = Money::from_i64(123_0000);
+    let client = 725;
+    let deposit_id: TxId = 101;
+    let mut account = Account::new(client);
+
+    let deposit = Transaction::new(
+        Action::try_new_deposit(deposit_amount).unwrap(),
+        client,
+        deposit_id,
+    );
+    assert_eq!(
+        Ok(()),
+        account.process_transaction(&deposit, &mut tx_history)
+    );
+    let dispute = Transaction::new(Action::new_dispute(), client, deposit_id);
+    assert_eq!(
+        Ok(()),
+        account.process_transaction(&dispute, &mut tx_history)
+    );
+
+    // some inconsistency has left less held than is disputed
+    account.held_funds = Money::from_i64(100_0000);
+
+    let chargeback = Transaction::new(Action::new_chargeback(), client, deposit_id);
+    assert_eq!(
+        Err(Error::ChargebackExceedsHeld(deposit_id)),
+        account.process_transaction(&chargeback, &mut tx_history)
+    );
+
+    // no change
+    assert_eq!(account.available_funds, Money::ZERO);
+    assert_eq!(account.held_funds, Money::from_i64(100_0000));
+    assert!(!account.locked);
+    let past_tx = tx_history.get_mut(deposit_id).unwrap();
+    assert!(past_tx.disputed);
+    assert_eq!(past_tx.amount, deposit_amount);
+}
+
+#[test]
+fn chargeback_of_partial_dispute_leaves_remainder() {
+    let mut tx_history = tx_history::TxHistory::default();
+    let deposit_amount = Money::from_i64(100_0000);
+    let disputed_amount = Money::from_i64(30_0000);
+    let client = 725;
+    let deposit_id: TxId = 101;
+    let mut account = Account::new(client);
+
+    let deposit = Transaction::new(
+        Action::try_new_deposit(deposit_amount).unwrap(),
+        client,
+        deposit_id,
+    );
+    assert_eq!(
+        Ok(()),
+        account.process_transaction(&deposit, &mut tx_history)
+    );
+
+    // disputing more than was deposited is rejected
+    let dispute = Transaction::new(
+        Action::try_new_partial_dispute(Money::from_i64(100_0001)).unwrap(),
+        client,
+        deposit_id,
+    );
+    assert_eq!(
+        Err(Error::DisputeExceedsAmount(deposit_id)),
+        account.process_transaction(&dispute, &mut tx_history)
+    );
+
+    let dispute = Transaction::new(
+        Action::try_new_partial_dispute(disputed_amount).unwrap(),
+        client,
+        deposit_id,
+    );
+    assert_eq!(
+        Ok(()),
+        account.process_transaction(&dispute, &mut tx_history)
+    );
+    assert_eq!(account.available_funds, Money::from_i64(70_0000));
+    assert_eq!(account.held_funds, disputed_amount);
+
+    // only the disputed portion is charged back
+    let chargeback = Transaction::new(Action::new_chargeback(), client, deposit_id);
+    assert_eq!(
+        Ok(()),
+        account.process_transaction(&chargeback, &mut tx_history)
+    );
+    assert_eq!(account.available_funds, Money::from_i64(70_0000));
+    assert_eq!(account.held_funds, Money::ZERO);
+    assert!(account.locked);
+
+    // and the remainder may still be disputed
+    let dispute = Transaction::new(Action::new_dispute(), client, deposit_id);
+    assert_eq!(
+        Ok(()),
+        account.process_transaction(&dispute, &mut tx_history)
+    );
+    assert_eq!(account.available_funds, Money::ZERO);
+    assert_eq!(account.held_funds, Money::from_i64(70_0000));
+}
+
+/// A `Ledger` that logs each call, for checking how accounts use their ledger
+#[derive(Default)]
+struct MockLedger {
+    txs: Vec<(TxId, CompletedTx)>,
+    calls: Vec<(&'static str, TxId)>,
+}
+
+impl Ledger for MockLedger {
+    fn record(&mut self, id: TxId, tx: CompletedTx) -> bool {
+        self.calls.push(("record", id));
+        if self.txs.iter().any(|&(recorded, _)| recorded == id) {
+            return false;
+        }
+        self.txs.push((id, tx));
+        true
+    }
+
+    fn get_mut(&mut self, id: TxId) -> Option<&mut CompletedTx> {
+        self.calls.push(("get_mut", id));
+        self.txs
+            .iter_mut()
+            .find(|(recorded, _)| *recorded == id)
+            .map(|(_, tx)| tx)
+    }
+}
+
+#[test]
+fn accounts_use_pluggable_ledger() {
+    let mut ledger = MockLedger::default();
+    let client = 725;
+    let mut account = Account::new(client);
+    let ten = Money::from_i64(10_0000);
+    for tx in [
+        Transaction::new(Action::try_new_deposit(ten).unwrap(), client, 1),
+        Transaction::new(Action::try_new_deposit(ten).unwrap(), client, 1),
+        Transaction::new(Action::try_new_withdrawal(ten).unwrap(), client, 2),
+        Transaction::new(Action::new_dispute(), client, 1),
+        Transaction::new(Action::new_resolve(), client, 3),
+    ] {
+        let _ = account.process_transaction(&tx, &mut ledger);
+    }
+    assert_eq!(
+        ledger.calls,
+        [
+            ("record", 1),
+            ("record", 1),
+            ("record", 2),
+            ("get_mut", 1),
+            ("get_mut", 3)
+        ]
+    );
+    // the dispute was applied to the ledger's own copy of the deposit
+    let mut expected = CompletedTx::new(client, CompletedTxKind::Deposit, ten);
+    expected.disputed = true;
+    expected.held = ten;
+    assert_eq!(
+        ledger.txs,
+        [
+            (1, expected),
+            (
+                2,
+                CompletedTx::new(client, CompletedTxKind::Withdrawal, ten)
+            )
+        ]
+    );
+    assert_eq!(account.balances(), (Money::from_i64(-10_0000), ten, false));
+}
+
+#[test]
+fn redispute_after_resolve_depends_on_policy() {
+    let client = 725;
+    let deposit_id = 1;
+    let amount = Money::from_i64(10_0000);
+    let deposit = Transaction::new(Action::try_new_deposit(amount).unwrap(), client, deposit_id);
+    let dispute = Transaction::new(Action::new_dispute(), client, deposit_id);
+    let resolve = Transaction::new(Action::new_resolve(), client, deposit_id);
+    for (policy, redispute) in [
+        (Policy::default(), Ok(())),
+        (
+            Policy {
+                forbid_redispute: true,
+                ..Policy::default()
+            },
+            Err(Error::AlreadyResolvedOnce(deposit_id)),
+        ),
+    ] {
+        let mut tx_history = tx_history::TxHistory::default();
+        let mut account = Account::new(client);
+        for tx in [&deposit, &dispute, &resolve] {
+            assert_eq!(
+                Ok(()),
+                account.process_transaction_with(tx, &mut tx_history, &policy)
+            );
+        }
+        assert_eq!(
+            redispute,
+            account.process_transaction_with(&dispute, &mut tx_history, &policy)
+        );
+        let held = if redispute.is_ok() {
+            amount
+        } else {
+            Money::ZERO
+        };
+        assert_eq!(account.held_funds, held);
+    }
+}
+
+#[test]
+fn zero_amounts_skipped_by_policy() {
+    let client = 12;
+    let zero_deposit = Transaction::new(Action::try_new_deposit(Money::ZERO).unwrap(), client, 1);
+    let zero_withdrawal =
+        Transaction::new(Action::try_new_withdrawal(Money::ZERO).unwrap(), client, 2);
+    let dispute = Transaction::new(Action::new_dispute(), client, 1);
+
+    // recorded by default, so disputable
+    let mut tx_history = tx_history::TxHistory::default();
+    let mut account = Account::new(client);
+    assert_eq!(
+        Ok(()),
+        account.process_transaction(&zero_deposit, &mut tx_history)
+    );
+    assert_eq!(
+        Ok(()),
+        account.process_transaction(&zero_withdrawal, &mut tx_history)
+    );
+    assert!(tx_history.contains(1) && tx_history.contains(2));
+    assert_eq!(
+        Ok(()),
+        account.process_transaction(&dispute, &mut tx_history)
+    );
+
+    let policy = Policy {
+        skip_zero_amounts: true,
+        ..Policy::default()
+    };
+    let mut tx_history = tx_history::TxHistory::default();
+    let mut account = Account::new(client);
+    for tx in [&zero_deposit, &zero_withdrawal] {
+        assert_eq!(
+            Ok(()),
+            account.process_transaction_with(tx, &mut tx_history, &policy)
+        );
+    }
+    assert!(!tx_history.contains(1) && !tx_history.contains(2));
+    assert_eq!(
+        Err(Error::UnknownTxReference(1)),
+        account.process_transaction_with(&dispute, &mut tx_history, &policy)
+    );
+    // and the ids remain free
+    let deposit = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(1_0000)).unwrap(),
+        client,
+        1,
+    );
+    assert_eq!(
+        Ok(()),
+        account.process_transaction_with(&deposit, &mut tx_history, &policy)
+    );
+    assert_eq!(account.available_funds, Money::from_i64(1_0000));
+}
+
+#[test]
+fn disputes_exceeding_max_held_lock_account() {
+    let mut tx_history = tx_history::TxHistory::default();
+    let client = 725;
+    let mut account = Account::new(client);
+    let policy = Policy {
+        max_held: Some(Money::from_i64(10_0000)),
+        ..Policy::default()
+    };
+    for (id, amount) in [(1, 6_0000), (2, 4_0000), (3, 1)] {
+        let deposit = Transaction::new(
+            Action::try_new_deposit(Money::from_i64(amount)).unwrap(),
+            client,
+            id,
+        );
+        assert_eq!(
+            Ok(()),
+            account.process_transaction_with(&deposit, &mut tx_history, &policy)
+        );
+    }
+
+    // holding exactly the cap is fine
+    for id in [1, 2] {
+        let dispute = Transaction::new(Action::new_dispute(), client, id);
+        assert_eq!(
+            Ok(()),
+            account.process_transaction_with(&dispute, &mut tx_history, &policy)
+        );
+    }
+    assert!(!account.is_locked());
+    assert_eq!(account.auto_locked_by(), None);
+
+    // but the dispute crossing it is applied, and locks the account
+    let dispute = Transaction::new(Action::new_dispute(), client, 3);
+    assert_eq!(
+        Ok(()),
+        account.process_transaction_with(&dispute, &mut tx_history, &policy)
+    );
+    assert_eq!(
+        account.balances(),
+        (Money::ZERO, Money::from_i64(10_0001), true)
+    );
+    assert_eq!(account.auto_locked_by(), Some(3));
+
+    // resolving doesn't unlock it
+    let resolve = Transaction::new(Action::new_resolve(), client, 3);
+    assert_eq!(
+        Ok(()),
+        account.process_transaction_with(&resolve, &mut tx_history, &policy)
+    );
+    assert!(account.is_locked());
+
+    // and without a cap, nothing is ever auto-locked
+    let mut tx_history = tx_history::TxHistory::default();
+    let mut account = Account::new(client);
+    let deposit = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(1_000_0000)).unwrap(),
+        client,
+        1,
+    );
+    let dispute = Transaction::new(Action::new_dispute(), client, 1);
+    assert_eq!(
+        Ok(()),
+        account.process_transaction(&deposit, &mut tx_history)
+    );
+    assert_eq!(
+        Ok(()),
+        account.process_transaction(&dispute, &mut tx_history)
+    );
+    assert!(!account.is_locked());
+}
+
+#[test]
+fn adjustments_credit_and_debit_when_allowed() {
+    let mut tx_history = tx_history::TxHistory::default();
+    let client = 725;
+    let mut account = Account::new(client);
+    let credit = Transaction::new(Action::new_adjustment(Money::from_i64(5_0000)), client, 1);
+    // forbidden by default
+    assert_eq!(
+        Err(Error::AdjustmentsForbidden(1)),
+        account.process_transaction(&credit, &mut tx_history)
+    );
+    assert!(!tx_history.contains(1));
+
+    let policy = Policy {
+        allow_adjustments: true,
+        ..Policy::default()
+    };
+    assert_eq!(
+        Ok(()),
+        account.process_transaction_with(&credit, &mut tx_history, &policy)
+    );
+    let debit = Transaction::new(Action::new_adjustment(Money::from_i64(-1_2500)), client, 2);
+    assert_eq!(
+        Ok(()),
+        account.process_transaction_with(&debit, &mut tx_history, &policy)
+    );
+    assert_eq!(account.available_funds, Money::from_i64(3_7500));
+
+    // recorded as adjustments, with their sign
+    let recorded = tx_history.get_mut(2).unwrap();
+    assert_eq!(recorded.kind, tx_history::CompletedTxKind::Adjustment);
+    assert_eq!(recorded.amount, Money::from_i64(-1_2500));
+
+    // never applied twice, nor disputed
+    assert_eq!(
+        Err(Error::DuplicateTransaction(2)),
+        account.process_transaction_with(&debit, &mut tx_history, &policy)
+    );
+    let dispute = Transaction::new(Action::new_dispute(), client, 1);
+    assert_eq!(
+        Err(Error::AdjustmentsAreIndisputable(1)),
+        account.process_transaction_with(&dispute, &mut tx_history, &policy)
+    );
+    assert_eq!(account.available_funds, Money::from_i64(3_7500));
+
+    // nor allowed to overflow
+    let huge = Transaction::new(Action::new_adjustment(Money::MAX), client, 3);
+    assert_eq!(
+        Err(Error::AdjustmentOverflows(3)),
+        account.process_transaction_with(&huge, &mut tx_history, &policy)
+    );
+    assert_eq!(account.available_funds, Money::from_i64(3_7500));
+}
+
+#[test]
+fn adjustments_may_be_negative() {
+    let data = [
+        ("type", "adjust"),
+        ("client", "615"),
+        ("tx", "100"),
+        ("amount", "-2.5"),
+    ];
+    assert_eq!(
+        parse_test_data(&data).unwrap(),
+        Transaction::new(Action::new_adjustment(Money::from_i64(-2_5000)), 615, 100)
+    );
+}
+
+#[test]
+fn withdrawals_keep_minimum_balance() {
+    let mut tx_history = tx_history::TxHistory::default();
+    let client = 725;
+    let mut account = Account::new(client);
+    let policy = Policy {
+        minimum_balance: Money::from_i64(10_0000),
+        ..Policy::default()
+    };
+    let deposit = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(50_0000)).unwrap(),
+        client,
+        1,
+    );
+    assert_eq!(
+        Ok(()),
+        account.process_transaction_with(&deposit, &mut tx_history, &policy)
+    );
+
+    // breaching the floor, while there are funds for it
+    let withdrawal = Transaction::new(
+        Action::try_new_withdrawal(Money::from_i64(40_0001)).unwrap(),
+        client,
+        2,
+    );
+    assert_eq!(
+        Err(Error::BelowMinimumBalance(2)),
+        account.process_transaction_with(&withdrawal, &mut tx_history, &policy)
+    );
+    // and without, which is still insufficient funds
+    let withdrawal = Transaction::new(
+        Action::try_new_withdrawal(Money::from_i64(60_0000)).unwrap(),
+        client,
+        3,
+    );
+    assert_eq!(
+        Err(Error::InsufficientFundsForWithdrawal(3)),
+        account.process_transaction_with(&withdrawal, &mut tx_history, &policy)
+    );
+    assert_eq!(account.available_funds, Money::from_i64(50_0000));
+
+    // leaving exactly the floor
+    let withdrawal = Transaction::new(
+        Action::try_new_withdrawal(Money::from_i64(40_0000)).unwrap(),
+        client,
+        4,
+    );
+    assert_eq!(
+        Ok(()),
+        account.process_transaction_with(&withdrawal, &mut tx_history, &policy)
+    );
+    assert_eq!(account.available_funds, Money::from_i64(10_0000));
+}
+
+#[test]
+fn references_distinguish_unrecorded_from_wrong_kind() {
+    let mut tx_history = tx_history::TxHistory::default();
+    let client = 11;
+    let mut account = Account::new(client);
+    let deposit = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(10_0000)).unwrap(),
+        client,
+        1,
+    );
+    let withdrawal = Transaction::new(
+        Action::try_new_withdrawal(Money::from_i64(2_0000)).unwrap(),
+        client,
+        2,
+    );
+    for tx in [&deposit, &withdrawal] {
+        assert_eq!(Ok(()), account.process_transaction(tx, &mut tx_history));
+    }
+
+    // never recorded, as a dispute's own row never is
+    for action in [
+        Action::new_dispute(),
+        Action::new_resolve(),
+        Action::new_chargeback(),
+    ] {
+        let reference = Transaction::new(action, client, 3);
+        assert_eq!(
+            Err(Error::UnknownTxReference(3)),
+            account.process_transaction(&reference, &mut tx_history)
+        );
+    }
+
+    // recorded, but a withdrawal, which is never in dispute
+    let dispute = Transaction::new(Action::new_dispute(), client, 2);
+    assert_eq!(
+        Err(Error::WithdrawalsAreIndisputable(2)),
+        account.process_transaction(&dispute, &mut tx_history)
+    );
+    for action in [Action::new_resolve(), Action::new_chargeback()] {
+        let reference = Transaction::new(action, client, 2);
+        assert_eq!(
+            Err(Error::IndisputableTxReferenced(2)),
+            account.process_transaction(&reference, &mut tx_history)
+        );
+    }
+    assert_eq!(account.available_funds, Money::from_i64(8_0000));
+}
+
+#[test]
+fn jpy_amounts_have_no_decimals() {
+    assert_eq!(currency::scale_of("JPY"), 0);
+    let yen = currency::parse_amount("JPY", "1500").unwrap();
+    assert_eq!(yen, Money::from_i64(1500_0000));
+    assert_eq!(currency::format_amount("jpy", yen), "1500");
+    // fractions of a yen are rounded away, half away from zero
+    assert_eq!(
+        currency::parse_amount("JPY", "1500.5"),
+        Ok(Money::from_i64(1501_0000))
+    );
+    assert_eq!(
+        currency::parse_amount("JPY", "-0.4"),
+        Ok(Money::from_i64(0))
+    );
+    assert_eq!(
+        currency::same_amount("JPY", Money::from_i64(7_4999), Money::from_i64(7_0000)),
+        Some(true)
+    );
+}
+
+#[test]
+fn bhd_amounts_have_three_decimals() {
+    assert_eq!(currency::scale_of("BHD"), 3);
+    let dinars = currency::parse_amount("BHD", "1.250").unwrap();
+    assert_eq!(dinars, Money::from_i64(1_2500));
+    assert_eq!(currency::format_amount("BHD", dinars), "1.250");
+    assert_eq!(
+        currency::parse_amount("BHD", "-1.2345"),
+        Ok(Money::from_i64(-1_2350))
+    );
+    assert_eq!(
+        currency::same_amount("BHD", Money::from_i64(1_2340), Money::from_i64(1_2350)),
+        Some(false)
+    );
+    // a single currency, or any unlisted, keeps the full scale
+    assert_eq!(currency::scale_of("XYZ"), Money::SCALE);
+    assert_eq!(
+        currency::parse_amount("XYZ", "1.23456"),
+        Ok(Money::from_i64(1_2346))
+    );
+}
+
+#[test]
+fn rounding_to_scale_near_the_limits() {
+    // rounding away from zero would pass them
+    assert_eq!(Money::MAX.round_to_scale(0), None);
+    assert_eq!(Money::MIN.round_to_scale(0), None);
+    assert_eq!(Money::MAX.round_to_scale(3), None);
+    assert_eq!(currency::same_amount("JPY", Money::MAX, Money::ZERO), None);
+    // but towards zero, or at the full scale, they're kept
+    assert_eq!(
+        Money::from_i64(i64::MAX - 5807).round_to_scale(0),
+        Some(Money::from_i64(i64::MAX - 5807))
+    );
+    assert_eq!(
+        Money::from_i64(i64::MAX - 7).round_to_scale(3),
+        Some(Money::from_i64(i64::MAX - 7))
+    );
+    assert_eq!(Money::MAX.round_to_scale(Money::SCALE), Some(Money::MAX));
+    assert_eq!(Money::MIN.round_to_scale(Money::SCALE), Some(Money::MIN));
+}
+
+#[test]
+fn duplicate_transactions_deduplicated_by_set() {
+    let deposit = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(1_5000)).unwrap(),
+        1,
+        1,
+    );
+    let same_id_other_amount = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(2_0000)).unwrap(),
+        1,
+        1,
+    );
+    let dispute = Transaction::new(Action::new_dispute(), 1, 1);
+    let transactions = [
+        deposit.clone(),
+        dispute.clone(),
+        deposit.clone(),
+        same_id_other_amount.clone(),
+        dispute.clone(),
+    ];
+    let unique: std::collections::HashSet<Transaction> = transactions.into_iter().collect();
+    assert_eq!(unique.len(), 3);
+    assert!(unique.contains(&deposit));
+    assert!(unique.contains(&same_id_other_amount));
+    assert!(unique.contains(&dispute));
+}
+
+#[test]
+fn idempotent_duplicates_ignore_matching_repeats() {
+    let policy = Policy {
+        idempotent_duplicates: true,
+        ..Policy::default()
+    };
+    let mut tx_history = tx_history::TxHistory::default();
+    let client = 725;
+    let mut account = Account::new(client);
+    let deposit = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(10_0000)).unwrap(),
+        client,
+        1,
+    );
+    let withdrawal = Transaction::new(
+        Action::try_new_withdrawal(Money::from_i64(8_0000)).unwrap(),
+        client,
+        2,
+    );
+    for tx in [&deposit, &withdrawal, &deposit, &withdrawal, &deposit] {
+        assert_eq!(
+            Ok(()),
+            account.process_transaction_with(tx, &mut tx_history, &policy)
+        );
+    }
+    // applied once each, though the repeated withdrawal no longer has the funds
+    assert_eq!(account.available_funds, Money::from_i64(2_0000));
+}
+
+#[test]
+fn idempotent_duplicates_reject_conflicting_repeats() {
+    let policy = Policy {
+        idempotent_duplicates: true,
+        ..Policy::default()
+    };
+    let mut tx_history = tx_history::TxHistory::default();
+    let client = 725;
+    let mut account = Account::new(client);
+    let deposit = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(10_0000)).unwrap(),
+        client,
+        1,
+    );
+    assert_eq!(
+        Ok(()),
+        account.process_transaction_with(&deposit, &mut tx_history, &policy)
+    );
+    for conflicting in [
+        Action::try_new_deposit(Money::from_i64(11_0000)).unwrap(),
+        Action::try_new_withdrawal(Money::from_i64(10_0000)).unwrap(),
+        Action::new_withdraw_all(),
+    ] {
+        let tx = Transaction::new(conflicting, client, 1);
+        assert_eq!(
+            Err(Error::DuplicateTransaction(1)),
+            account.process_transaction_with(&tx, &mut tx_history, &policy)
+        );
+    }
+    // and the same deposit, but into another client's account
+    let mut other = Account::new(client + 1);
+    let tx = Transaction::new(deposit.action(), client + 1, 1);
+    assert_eq!(
+        Err(Error::DuplicateTransaction(1)),
+        other.process_transaction_with(&tx, &mut tx_history, &policy)
+    );
+    assert_eq!(account.available_funds, Money::from_i64(10_0000));
+    assert_eq!(other.available_funds, Money::ZERO);
+}
+
+#[test]
+fn transaction_debug_is_one_line() {
+    let deposit = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(1_5000)).unwrap(),
+        1,
+        2,
+    );
+    assert_eq!(
+        format!("{:?}", deposit),
+        "Transaction { type: deposit, client: 1, tx: 2, amount: 1.5000 }"
+    );
+    let dispute = Transaction::new(Action::new_dispute(), 1, 2);
+    assert_eq!(
+        format!("{:?}", dispute),
+        "Transaction { type: dispute, client: 1, tx: 2 }"
+    );
+    let partial = Transaction::new(
+        Action::try_new_partial_dispute(Money::from_i64(5000)).unwrap(),
+        1,
+        2,
+    );
+    assert_eq!(
+        format!("{:?}", partial),
+        "Transaction { type: dispute, client: 1, tx: 2, amount: 0.5000 }"
+    );
+}
+
+#[test]
+fn statement_lists_a_clients_transactions_by_id() {
+    let mut tx_history = tx_history::TxHistory::default();
+    let mut account = Account::new(1);
+    let mut other = Account::new(2);
+    for tx in [
+        Transaction::new(
+            Action::try_new_deposit(Money::from_i64(5_0000)).unwrap(),
+            1,
+            30,
+        ),
+        Transaction::new(
+            Action::try_new_deposit(Money::from_i64(9_0000)).unwrap(),
+            2,
+            20,
+        ),
+        Transaction::new(
+            Action::try_new_deposit(Money::from_i64(2_0000)).unwrap(),
+            1,
+            10,
+        ),
+        Transaction::new(
+            Action::try_new_withdrawal(Money::from_i64(1_5000)).unwrap(),
+            1,
+            40,
+        ),
+        Transaction::new(Action::new_dispute(), 1, 10),
+    ] {
+        let account = if tx.client() == 1 {
+            &mut account
+        } else {
+            &mut other
+        };
+        assert_eq!(Ok(()), account.process_transaction(&tx, &mut tx_history));
+    }
+    let mut disputed = CompletedTx::new(1, CompletedTxKind::Deposit, Money::from_i64(2_0000));
+    disputed.disputed = true;
+    disputed.held = Money::from_i64(2_0000);
+    assert_eq!(
+        tx_history.statement(1),
+        [
+            (10, &disputed),
+            (
+                30,
+                &CompletedTx::new(1, CompletedTxKind::Deposit, Money::from_i64(5_0000))
+            ),
+            (
+                40,
+                &CompletedTx::new(1, CompletedTxKind::Withdrawal, Money::from_i64(1_5000))
+            ),
+        ]
+    );
+    assert!(tx_history.statement(3).is_empty());
+}
+
+#[test]
+fn overdrawing_disputes_rejected_when_forbidden() {
+    let client = 725;
+    let deposit = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(10_0000)).unwrap(),
+        client,
+        1,
+    );
+    let withdrawal = Transaction::new(
+        Action::try_new_withdrawal(Money::from_i64(6_0000)).unwrap(),
+        client,
+        2,
+    );
+    let dispute = Transaction::new(Action::new_dispute(), client, 1);
+
+    // by default, the dispute holds more than is available
+    let mut tx_history = tx_history::TxHistory::default();
+    let mut account = Account::new(client);
+    for tx in [&deposit, &withdrawal, &dispute] {
+        assert_eq!(Ok(()), account.process_transaction(tx, &mut tx_history));
+    }
+    assert_eq!(account.available_funds, Money::from_i64(-6_0000));
+
+    let policy = Policy {
+        forbid_overdrawing_disputes: true,
+        ..Policy::default()
+    };
+    let mut tx_history = tx_history::TxHistory::default();
+    let mut account = Account::new(client);
+    for tx in [&deposit, &withdrawal] {
+        assert_eq!(
+            Ok(()),
+            account.process_transaction_with(tx, &mut tx_history, &policy)
+        );
+    }
+    assert_eq!(
+        Err(Error::DisputeWouldOverdraw(1)),
+        account.process_transaction_with(&dispute, &mut tx_history, &policy)
+    );
+    assert_eq!(account.available_funds, Money::from_i64(4_0000));
+    assert_eq!(account.held_funds, Money::ZERO);
+    assert!(!tx_history.get(1).unwrap().disputed);
+    // a partial dispute within the available funds, leaving exactly zero, is still applied
+    let partial = Transaction::new(
+        Action::try_new_partial_dispute(Money::from_i64(4_0000)).unwrap(),
+        client,
+        1,
+    );
+    assert_eq!(
+        Ok(()),
+        account.process_transaction_with(&partial, &mut tx_history, &policy)
+    );
+    assert_eq!(account.available_funds, Money::ZERO);
+    assert_eq!(account.held_funds, Money::from_i64(4_0000));
+}
+
+#[test]
+fn eur_and_usd_balances_converted_to_usd_equivalent() {
+    let rates = currency::Rates::read(
+        "currency,rate\n\
+         USD,1.0\n\
+         eur,1.0832\n"
+            .as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(rates.rate("EUR"), Some(Money::from_i64(1_0832)));
+    // 100 EUR and 25.50 USD
+    let balances = [
+        ("EUR", Money::from_i64(100_0000)),
+        ("USD", Money::from_i64(25_5000)),
+    ];
+    assert_eq!(rates.equivalent(balances), Ok(Money::from_i64(133_8200)));
+    assert_eq!(
+        rates.equivalent([("GBP", Money::from_i64(1_0000))]),
+        Err(currency::ConversionError::MissingRate("GBP".to_owned()))
+    );
+    // the conversion, or the sum of conversions, overflowing
+    let huge = Money::from_i64(i64::MAX / 2);
+    assert_eq!(
+        rates.equivalent([("EUR", Money::MAX)]),
+        Err(currency::ConversionError::Overflow)
+    );
+    assert_eq!(
+        rates.equivalent([("USD", huge), ("USD", huge), ("USD", huge)]),
+        Err(currency::ConversionError::Overflow)
+    );
+}
+
+#[test]
+fn empty_account_closed_rejecting_everything_after() {
+    let client = 1;
+    let policy = Policy {
+        allow_closing: true,
+        ..Policy::default()
+    };
+    let mut tx_history = tx_history::TxHistory::default();
+    let mut account = Account::new(client);
+    let close = Transaction::new(Action::new_close(), client, 2);
+    // not without the policy allowing it
+    assert_eq!(
+        Err(Error::ClosingForbidden(2)),
+        account.process_transaction(&close, &mut tx_history)
+    );
+    assert!(!account.is_closed());
+
+    for tx in [
+        Transaction::new(
+            Action::try_new_deposit(Money::from_i64(10_0000)).unwrap(),
+            client,
+            1,
+        ),
+        Transaction::new(Action::new_withdraw_all(), client, 3),
+    ] {
+        assert_eq!(
+            Ok(()),
+            account.process_transaction_with(&tx, &mut tx_history, &policy)
+        );
+    }
+    assert_eq!(
+        Ok(()),
+        account.process_transaction_with(&close, &mut tx_history, &policy)
+    );
+    assert!(account.is_closed());
+
+    for tx in [
+        Transaction::new(
+            Action::try_new_deposit(Money::from_i64(5_0000)).unwrap(),
+            client,
+            4,
+        ),
+        Transaction::new(Action::new_dispute(), client, 1),
+        Transaction::new(Action::new_close(), client, 5),
+    ] {
+        assert_eq!(
+            Err(Error::AccountClosed(tx.id())),
+            account.process_transaction_with(&tx, &mut tx_history, &policy)
+        );
+    }
+    assert_eq!(account.balances(), (Money::ZERO, Money::ZERO, false));
+    assert!(tx_history.get(4).is_none());
+    assert!(!tx_history.get(1).unwrap().disputed);
+}
+
+#[test]
+fn non_empty_account_not_closed() {
+    let client = 1;
+    let policy = Policy {
+        allow_closing: true,
+        ..Policy::default()
+    };
+    let mut tx_history = tx_history::TxHistory::default();
+    let mut account = Account::new(client);
+    let deposit = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(10_0000)).unwrap(),
+        client,
+        1,
+    );
+    let dispute = Transaction::new(Action::new_dispute(), client, 1);
+    let close = Transaction::new(Action::new_close(), client, 2);
+    assert_eq!(
+        Ok(()),
+        account.process_transaction_with(&deposit, &mut tx_history, &policy)
+    );
+    assert_eq!(
+        Err(Error::CloseOfNonEmptyAccount(2)),
+        account.process_transaction_with(&close, &mut tx_history, &policy)
+    );
+    // held funds are still funds in the account
+    assert_eq!(
+        Ok(()),
+        account.process_transaction_with(&dispute, &mut tx_history, &policy)
+    );
+    assert_eq!(account.available_funds, Money::ZERO);
+    assert_eq!(
+        Err(Error::CloseOfNonEmptyAccount(2)),
+        account.process_transaction_with(&close, &mut tx_history, &policy)
+    );
+    assert!(!account.is_closed());
+    assert_eq!(account.held_funds, Money::from_i64(10_0000));
+}
@@ -1,13 +1,15 @@
 //! `Transaction` represents an operation on an [`Account`](super::Account),
-//! that is deserializable from CSV and applied in
+//! that is (de)serializable as a CSV record and applied in
 //! [`Account::process_transaction`](super::Account::process_transaction)
 //!
 use super::{Client, Money};
-use serde::{de, Deserialize};
+use serde::{de, ser, ser::SerializeStruct, Deserialize, Serialize};
 
 pub type Id = u32;
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// Equal transactions, and so equal hashes, are exact duplicates: the same id, client,
+/// action, and amount, such as to drop repeated records with a `HashSet` before processing.
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Transaction {
     action: Action,
     client: Client,
@@ -30,6 +32,36 @@ impl Transaction {
     }
 }
 
+// on one line, as the record would be, rather than the nested `Action`, for readable test
+// failures: `Transaction { type: deposit, client: 1, tx: 2, amount: 1.5 }`
+impl std::fmt::Debug for Transaction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Transaction {{ type: {}, client: {}, tx: {}",
+            self.action.type_name(),
+            self.client,
+            self.id
+        )?;
+        let amount = match self.action {
+            Action::Deposit { amount }
+            | Action::Withdrawal { amount }
+            | Action::Adjust { amount }
+            | Action::PartialDispute { amount } => Some(amount),
+            Action::Custom { amount, .. } => amount,
+            Action::WithdrawAll
+            | Action::Dispute
+            | Action::Resolve
+            | Action::Chargeback
+            | Action::Close => None,
+        };
+        if let Some(amount) = amount {
+            write!(f, ", amount: {}", amount)?;
+        }
+        f.write_str(" }")
+    }
+}
+
 // manual impl of Deser for Tx is required because of csv's poor reaction to #[serde(flatten)]
 // (csv uses infer_deserialize for the child struct, converting 'amount' to f64)
 impl<'de> Deserialize<'de> for Transaction {
@@ -41,19 +73,108 @@ impl<'de> Deserialize<'de> for Transaction {
         struct CsvTransaction {
             #[serde(rename = "type")]
             action_type: ActionType,
+            #[serde(default, deserialize_with = "blank_as_none")]
             amount: Option<Money>,
             client: Client,
             #[serde(rename = "tx")]
             id: Id,
         }
-        #[derive(Deserialize)]
-        #[serde(rename_all = "lowercase")]
         enum ActionType {
             Deposit,
             Withdrawal,
+            WithdrawAll,
+            Adjust,
             Dispute,
             Resolve,
             Chargeback,
+            Close,
+        }
+        // canonically lowercase, but some exports use title or upper case
+        impl<'de> Deserialize<'de> for ActionType {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                struct ActionTypeVisitor;
+                impl<'de> de::Visitor<'de> for ActionTypeVisitor {
+                    type Value = ActionType;
+
+                    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        formatter.write_str("a transaction type")
+                    }
+
+                    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+                    where
+                        E: de::Error,
+                    {
+                        const VARIANTS: &[&str] = Action::TYPES;
+                        let is = |variant: &str| v.eq_ignore_ascii_case(variant);
+                        Ok(if is("deposit") {
+                            ActionType::Deposit
+                        } else if is("withdrawal") {
+                            ActionType::Withdrawal
+                        } else if is("withdraw_all") {
+                            ActionType::WithdrawAll
+                        } else if is("adjust") {
+                            ActionType::Adjust
+                        } else if is("dispute") {
+                            ActionType::Dispute
+                        } else if is("resolve") {
+                            ActionType::Resolve
+                        } else if is("chargeback") {
+                            ActionType::Chargeback
+                        } else if is("close") {
+                            ActionType::Close
+                        } else {
+                            return Err(de::Error::unknown_variant(v, VARIANTS));
+                        })
+                    }
+                }
+                deserializer.deserialize_str(ActionTypeVisitor)
+            }
+        }
+
+        // a whitespace-only amount is as missing as an empty one, whether or not the reader
+        // trims fields, so check the field's str before parsing it as Money, in place,
+        // without copying it out of the record
+        fn blank_as_none<'de, D>(deserializer: D) -> Result<Option<Money>, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            struct BlankAsNoneVisitor;
+            impl<'de> de::Visitor<'de> for BlankAsNoneVisitor {
+                type Value = Option<Money>;
+
+                fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    formatter.write_str("an amount of money, or nothing")
+                }
+
+                fn visit_none<E>(self) -> Result<Self::Value, E> {
+                    Ok(None)
+                }
+
+                fn visit_unit<E>(self) -> Result<Self::Value, E> {
+                    Ok(None)
+                }
+
+                fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    deserializer.deserialize_str(self)
+                }
+
+                fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+                where
+                    E: de::Error,
+                {
+                    if v.trim().is_empty() {
+                        return Ok(None);
+                    }
+                    Money::deserialize(de::value::StrDeserializer::<E>::new(v)).map(Some)
+                }
+            }
+            deserializer.deserialize_option(BlankAsNoneVisitor)
         }
 
         // csv and #[serde(flatten)] don't mix well, so deserialize a flat copy of the struct
@@ -63,31 +184,33 @@ impl<'de> Deserialize<'de> for Transaction {
             client,
             id,
         } = CsvTransaction::deserialize(deserializer)?;
-        // and then un-flatten it
-        let mut take_amount = || {
-            std::mem::take(&mut amount)
-                .ok_or_else(|| de::Error::missing_field("amount"))
-                .and_then(|money| {
-                    if money.is_negative() {
-                        Err(de::Error::invalid_value(
-                            serde::de::Unexpected::Float(money.to_f64()),
-                            &"a positive amount of moneys",
-                        ))
-                    } else {
-                        Ok(money)
-                    }
-                })
+        // and then un-flatten it.
+        // `amount` is None when the field is empty ("deposit,1,1,") or only whitespace
+        // ("deposit,1,1,  "), and when a short record omits it altogether ("deposit,1,1",
+        // accepted by flexible readers),
+        // so both are rejected as a missing field for deposits and withdrawals.
+        let mut take_amount =
+            || std::mem::take(&mut amount).ok_or_else(|| de::Error::missing_field("amount"));
+        let invalid = |ValidationError::NegativeAmount(money): ValidationError| {
+            // rather than a misleadingly rounded float
+            let exact = money.to_string();
+            let unexpected = money
+                .try_to_f64_lossless()
+                .map_or(de::Unexpected::Str(&exact), de::Unexpected::Float);
+            de::Error::invalid_value(unexpected, &"a positive amount of moneys")
         };
         let action = match action_type {
-            ActionType::Deposit => Action::Deposit {
-                amount: take_amount()?,
-            },
-            ActionType::Withdrawal => Action::Withdrawal {
-                amount: take_amount()?,
-            },
+            ActionType::Deposit => Action::try_new_deposit(take_amount()?).map_err(invalid)?,
+            ActionType::Withdrawal => {
+                Action::try_new_withdrawal(take_amount()?).map_err(invalid)?
+            }
+            ActionType::WithdrawAll => Action::WithdrawAll,
+            // adjustments are either credits or debits, so may be negative
+            ActionType::Adjust => Action::new_adjustment(take_amount()?),
             ActionType::Dispute => Action::Dispute,
             ActionType::Resolve => Action::Resolve,
             ActionType::Chargeback => Action::Chargeback,
+            ActionType::Close => Action::Close,
         };
         // whether or not we've called take_amount, amount should now be None.
         if amount.is_some() {
@@ -98,32 +221,172 @@ impl<'de> Deserialize<'de> for Transaction {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+// the canonical record of a transaction, as read by the Deserialize impl above.
+// Partial disputes have no record, and so fail to serialize.
+impl Serialize for Transaction {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let (action_type, amount) = match self.action {
+            Action::Deposit { amount } => ("deposit", Some(amount)),
+            Action::Withdrawal { amount } => ("withdrawal", Some(amount)),
+            Action::WithdrawAll => ("withdraw_all", None),
+            Action::Adjust { amount } => ("adjust", Some(amount)),
+            Action::Custom { name, amount } => (name, amount),
+            Action::Dispute => ("dispute", None),
+            Action::PartialDispute { .. } => {
+                return Err(ser::Error::custom(
+                    "partial disputes can't be expressed as transaction records",
+                ))
+            }
+            Action::Resolve => ("resolve", None),
+            Action::Chargeback => ("chargeback", None),
+            Action::Close => ("close", None),
+        };
+        let mut state = serializer.serialize_struct("Transaction", 4)?;
+        state.serialize_field("type", action_type)?;
+        state.serialize_field("client", &self.client)?;
+        state.serialize_field("tx", &self.id)?;
+        state.serialize_field("amount", &amount)?;
+        state.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Action {
-    Deposit { amount: Money },
-    Withdrawal { amount: Money },
+    Deposit {
+        amount: Money,
+    },
+    Withdrawal {
+        amount: Money,
+    },
+    /// Withdraws the whole of the available funds, such as to close the account.
+    /// The amount actually withdrawn is recorded, as for any other withdrawal.
+    WithdrawAll,
+    /// Credits, or if `amount` is negative debits, the available funds directly, such as to
+    /// correct a bank error. Only applied when the [`Policy`](super::Policy) allows it.
+    Adjust {
+        amount: Money,
+    },
     Dispute,
+    /// Disputes only `amount` of the referenced deposit.
+    /// Transaction records can't express partial disputes, only the library API can.
+    PartialDispute {
+        amount: Money,
+    },
     Resolve,
     Chargeback,
+    /// Closes the account, if nothing is available or held, rejecting every transaction after.
+    /// Only applied when the [`Policy`](super::Policy) allows it.
+    Close,
+    /// A transaction type registered by `name` with an [`ActionHandler`](super::ActionHandler),
+    /// which alone knows what to make of the `amount`.
+    Custom {
+        name: &'static str,
+        amount: Option<Money>,
+    },
+}
+
+impl Action {
+    /// The `type` of every built in transaction record
+    pub const TYPES: &'static [&'static str] = &[
+        "deposit",
+        "withdrawal",
+        "withdraw_all",
+        "adjust",
+        "dispute",
+        "resolve",
+        "chargeback",
+        "close",
+    ];
+
+    /// The `type` of the action's transaction record, one of [`Action::TYPES`] or the name of a
+    /// custom action. Partial disputes have no record of their own, but are disputes.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Action::Deposit { .. } => "deposit",
+            Action::Withdrawal { .. } => "withdrawal",
+            Action::WithdrawAll => "withdraw_all",
+            Action::Adjust { .. } => "adjust",
+            Action::Dispute | Action::PartialDispute { .. } => "dispute",
+            Action::Resolve => "resolve",
+            Action::Chargeback => "chargeback",
+            Action::Close => "close",
+            Action::Custom { name, .. } => name,
+        }
+    }
 }
 
+/// Why an [`Action`] couldn't be constructed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    /// deposits, withdrawals, and partial disputes are of positive amounts
+    NegativeAmount(Money),
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::NegativeAmount(amount) => {
+                write!(f, "expected a positive amount of moneys, got {}", amount)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Rejects negative amounts, for actions that have a direction of their own
+fn non_negative(amount: Money) -> Result<Money, ValidationError> {
+    if amount.is_negative() {
+        Err(ValidationError::NegativeAmount(amount))
+    } else {
+        Ok(amount)
+    }
+}
+
+// the try_new_ constructors return an error for negative amounts; the new_ constructors of
+// actions with amounts panic instead, so are deprecated in favour of them.
 #[allow(unused)]
 impl Action {
+    pub fn try_new_deposit(amount: Money) -> Result<Self, ValidationError> {
+        non_negative(amount).map(|amount| Action::Deposit { amount })
+    }
+    pub fn try_new_withdrawal(amount: Money) -> Result<Self, ValidationError> {
+        non_negative(amount).map(|amount| Action::Withdrawal { amount })
+    }
+    pub fn try_new_partial_dispute(amount: Money) -> Result<Self, ValidationError> {
+        non_negative(amount).map(|amount| Action::PartialDispute { amount })
+    }
+    #[deprecated(note = "panics on negative amounts, use `try_new_deposit`")]
     pub fn new_deposit(amount: Money) -> Self {
-        assert!(!amount.is_negative());
-        Action::Deposit { amount }
+        Self::try_new_deposit(amount).unwrap()
     }
+    #[deprecated(note = "panics on negative amounts, use `try_new_withdrawal`")]
     pub fn new_withdrawal(amount: Money) -> Self {
-        assert!(!amount.is_negative());
-        Action::Withdrawal { amount }
+        Self::try_new_withdrawal(amount).unwrap()
+    }
+    pub fn new_withdraw_all() -> Self {
+        Action::WithdrawAll
+    }
+    pub fn new_adjustment(amount: Money) -> Self {
+        Action::Adjust { amount }
     }
     pub fn new_dispute() -> Self {
         Action::Dispute
     }
+    #[deprecated(note = "panics on negative amounts, use `try_new_partial_dispute`")]
+    pub fn new_partial_dispute(amount: Money) -> Self {
+        Self::try_new_partial_dispute(amount).unwrap()
+    }
     pub fn new_resolve() -> Self {
         Action::Resolve
     }
     pub fn new_chargeback() -> Self {
         Action::Chargeback
     }
+    pub fn new_close() -> Self {
+        Action::Close
+    }
 }
@@ -1,45 +1,145 @@
 //! A history of account deposits and withdrawals to facilitate disputes and chargebacks.
 //!
-use super::{Money, TxId};
+use super::{Client, HashState, Ledger, Money, TxId};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Default)]
-pub struct TxHistory(HashMap<TxId, CompletedTx>);
+#[derive(Clone, Default)]
+pub struct TxHistory(HashMap<TxId, CompletedTx, HashState>);
 
 impl TxHistory {
-    pub(super) fn record_transaction(
-        &mut self,
-        id: TxId,
-        amount: Money,
-        kind: CompletedTxKind,
-    ) -> Result<(), ()> {
-        let entry = self.0.entry(id);
+    pub fn with_hasher(hash_state: HashState) -> Self {
+        Self(HashMap::with_hasher(hash_state))
+    }
+
+    /// Every recorded transaction, in no particular order
+    pub fn entries(&self) -> impl Iterator<Item = (TxId, &CompletedTx)> {
+        self.0.iter().map(|(&id, tx)| (id, tx))
+    }
+
+    /// Every transaction recorded for `client`, sorted by id, as a mini statement of their
+    /// deposits, withdrawals, and adjustments, along with the state of any disputes of them
+    pub fn statement(&self, client: Client) -> Vec<(TxId, &CompletedTx)> {
+        let mut statement: Vec<_> = self
+            .entries()
+            .filter(|(_, tx)| tx.client == client)
+            .collect();
+        statement.sort_unstable_by_key(|&(id, _)| id);
+        statement
+    }
+
+    /// Every recorded transaction, in no particular order, consuming the history
+    pub(crate) fn into_entries(self) -> impl Iterator<Item = (TxId, CompletedTx)> {
+        self.0.into_iter()
+    }
+
+    /// The number of recorded transactions
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub(crate) fn contains(&self, id: TxId) -> bool {
+        self.0.contains_key(&id)
+    }
+
+    pub(crate) fn get(&self, id: TxId) -> Option<&CompletedTx> {
+        self.0.get(&id)
+    }
+
+    /// Forgets a recorded transaction, such as one rolled back
+    pub(crate) fn remove(&mut self, id: TxId) {
+        self.0.remove(&id);
+    }
+
+    /// Inserts a previously recorded transaction, such as one restored from a snapshot,
+    /// replacing any transaction with the same id.
+    pub(crate) fn restore(&mut self, id: TxId, tx: CompletedTx) {
+        self.0.insert(id, tx);
+    }
+
+    /// Every transaction currently in dispute, as `(id, client, held)`, sorted by id
+    pub(crate) fn disputed(&self) -> Vec<(TxId, Client, Money)> {
+        let mut disputed: Vec<_> = self
+            .0
+            .iter()
+            .filter(|(_, tx)| tx.disputed)
+            .map(|(&id, tx)| (id, tx.client, tx.held))
+            .collect();
+        disputed.sort_unstable_by_key(|&(id, _, _)| id);
+        disputed
+    }
+
+    /// Sums each client's recorded deposits less their recorded withdrawals.
+    ///
+    /// Charged back deposits have been zeroed, and so don't count towards the sum.
+    pub(crate) fn net_recorded_by_client(&self) -> HashMap<Client, Money> {
+        let mut net = HashMap::new();
+        for tx in self.0.values() {
+            let sum = net.entry(tx.client).or_insert(Money::ZERO);
+            match tx.kind {
+                CompletedTxKind::Deposit => *sum += tx.amount,
+                CompletedTxKind::Withdrawal => *sum -= tx.amount,
+                CompletedTxKind::Adjustment => *sum += tx.amount,
+            }
+        }
+        net
+    }
+}
+
+impl Ledger for TxHistory {
+    fn record(&mut self, id: TxId, tx: CompletedTx) -> bool {
         use std::collections::hash_map::Entry::*;
-        match entry {
-            Occupied(_) => Err(()),
+        match self.0.entry(id) {
+            Occupied(_) => false,
             Vacant(v) => {
-                v.insert(CompletedTx {
-                    kind,
-                    amount,
-                    disputed: false,
-                });
-                Ok(())
+                v.insert(tx);
+                true
             }
         }
     }
 
-    pub(super) fn past_transaction(&mut self, id: TxId) -> Option<&mut CompletedTx> {
+    fn get_mut(&mut self, id: TxId) -> Option<&mut CompletedTx> {
         self.0.get_mut(&id)
     }
 }
 
-pub(super) struct CompletedTx {
+/// A deposit or withdrawal as recorded in a [`Ledger`], along with the state of any dispute
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompletedTx {
+    /// the client whose account the transaction was applied to
+    pub client: Client,
     pub kind: CompletedTxKind,
     pub amount: Money,
     pub disputed: bool,
+    /// the disputed portion of `amount`, held while `disputed`
+    pub held: Money,
+    /// has the whole of `amount` been charged back?
+    pub charged_back: bool,
+    /// has a dispute of the transaction ever been resolved?
+    #[serde(default)]
+    pub resolved_once: bool,
+}
+
+impl CompletedTx {
+    /// A newly completed transaction, not yet disputed
+    pub fn new(client: Client, kind: CompletedTxKind, amount: Money) -> Self {
+        Self {
+            client,
+            kind,
+            amount,
+            disputed: false,
+            held: Money::ZERO,
+            charged_back: false,
+            resolved_once: false,
+        }
+    }
 }
 
-pub(super) enum CompletedTxKind {
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompletedTxKind {
     Withdrawal,
     Deposit,
+    /// a credit, or with a negative amount a debit, correcting the available funds
+    Adjustment,
 }
@@ -0,0 +1,647 @@
+//! Commandline argument parsing
+use payment_toy::{
+    account::{Action, Money},
+    engine::{Config, ReportOptions},
+};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: payment_toy [options] <transactions.csv>
+       payment_toy verify-snapshot <snapshot.json>
+       payment_toy serve-uds <socket>
+       payment_toy report-schema json
+       payment_toy replay --from-snapshot <start.json> --expect <end.json> <transactions.csv>
+
+Options:
+  --merge-into <report.csv>          merge into an existing report, rather than printing it
+  --snapshot <snapshot.json>         resume from a snapshot saved by --save-snapshot
+  --save-snapshot <snapshot.json>    save a snapshot of the accounts and history
+  --since-snapshot <snapshot.json>   resume from a snapshot, reporting only accounts changed since
+  --reconciliation <totals.csv>      write the sums of transactions and balances
+  --dump-history <history.csv>       write every recorded transaction and whether it's disputed
+  --applied-ids <applied.txt>        skip deposits and withdrawals applied in prior runs
+  --audit                            warn of funds exceeding recorded deposits
+  --tee <report.csv>                 write the report to report.csv as well as stdout
+  --format csv|json|jsonl            the format of the report
+  --pretty                           indent JSON reports
+  --no-total                         leave the total column out of the report
+  --compact                          write amounts with as few decimal places as they need
+  --limit <n>                        report only the first n accounts
+  --report-locked-only               report only locked accounts
+  --output-bom                       start the CSV report with a UTF-8 byte order mark
+  --flush-every <n>                  flush the CSV report after every n accounts
+  --max-amount <amount>              reject deposits and withdrawals of more than amount
+  --minimum-balance <amount>         reject withdrawals leaving less than amount available
+  --no-negative                      reject disputes leaving negative available funds
+  --forbid-redispute                 reject disputes of transactions already resolved
+  --allow-deposits-when-locked       accept deposits into locked accounts
+  --max-held <amount>                lock accounts whose disputes hold more than amount
+  --max-total-held <amount>          reject disputes holding more than amount across all accounts
+  --allow-adjustments                apply adjust transactions, crediting or debiting directly
+  --allow-closing                    apply close transactions to empty accounts, reporting closed
+  --skip-zero-amounts                accept deposits and withdrawals of zero unrecorded
+  --idempotent-duplicates            accept repeats of recorded transactions without reapplying
+  --reject-rounded-to-zero           reject withdrawals too small to keep, such as 0.00004
+  --max-frac-digits <n>              fail on amounts with more than n fractional digits
+  --allow-digit-separators           accept underscores between digits of amounts, as in 1_000.5
+  --warn-forward-references          warn of disputes referencing ids not yet seen
+  --validate-references              fail before processing if disputes reference missing ids
+  --check-sequences                  warn of repeated disputes, resolves, and chargebacks first
+  --keep-implied-accounts            report clients whose only transactions were rejected disputes
+  --max-memory <bytes>               fail before the accounts and history would use more memory
+  --alert-overdraft <amount>         warn of disputes leaving available funds below -amount
+  --column-alias <from>=<to>         rename an input column, may be repeated
+  --only <type>,...                  apply only transactions of these types, skipping the rest
+  --strict-headers                   fail unless the columns are exactly type, client, tx, amount
+  --dead-letter <rejected.csv>       write rejected records, and the reasons why
+  --strict                           fail at the first rejected transaction
+  --reverse                          undo the transactions, last first, from a snapshot after them
+  --error-format text|json           the format of a failure, printed to stderr
+  --normalize                        print the applied transactions, rather than the report
+  --count                            print counts of the transactions, rather than the report
+  --quiet                            print nothing but the report
+  --trim none|headers|fields|all     which whitespace to trim from the input
+  --comment <char>                   skip input lines starting with char, such as #
+  --delimiter <char>                 the character separating the input's fields
+  --decimal-sep <char>               the decimal point of the input's amounts
+  --currency <code>                  round amounts to the currency's decimal places, as JPY's none
+  --rates <rates.csv>                add totals converted from the --currency by currency,rate
+  --hash-seed <n>                    hash with a fixed seed, for reproducible internal order";
+
+/// What the commandline asked for
+pub enum Command {
+    /// process transactions, and print the report
+    Process(Box<Options>),
+    /// check a snapshot's invariants
+    VerifySnapshot(PathBuf),
+    /// check that transactions lead from one snapshot to another
+    Replay(Replay),
+    /// apply newline-delimited JSON transactions received over a Unix domain socket
+    ServeUds(PathBuf),
+    /// print the report's columns and their types, processing nothing
+    ReportSchema,
+}
+
+/// The arguments to `replay`
+pub struct Replay {
+    /// the snapshot to start from
+    pub from_snapshot: PathBuf,
+    /// the snapshot the accounts are expected to end up matching
+    pub expect: PathBuf,
+    /// the transactions to replay
+    pub input: PathBuf,
+}
+
+impl Command {
+    pub fn from_args(args: impl IntoIterator<Item = OsString>) -> Result<Self, String> {
+        let mut args = args.into_iter().peekable();
+        if args.peek().and_then(|arg| arg.to_str()) == Some("verify-snapshot") {
+            args.next();
+            let snapshot = args
+                .next()
+                .ok_or_else(|| format!("verify-snapshot expects a snapshot!\n{}", USAGE))?;
+            if args.next().is_some() {
+                return Err(format!("Too many arguments to verify-snapshot!\n{}", USAGE));
+            }
+            return Ok(Command::VerifySnapshot(PathBuf::from(snapshot)));
+        }
+        if args.peek().and_then(|arg| arg.to_str()) == Some("serve-uds") {
+            args.next();
+            let socket = args
+                .next()
+                .ok_or_else(|| format!("serve-uds expects a socket path!\n{}", USAGE))?;
+            if args.next().is_some() {
+                return Err(format!("Too many arguments to serve-uds!\n{}", USAGE));
+            }
+            return Ok(Command::ServeUds(PathBuf::from(socket)));
+        }
+        if args.peek().and_then(|arg| arg.to_str()) == Some("report-schema") {
+            args.next();
+            // JSON is the only format, but naming it leaves room for others
+            if args.next().as_deref().and_then(|arg| arg.to_str()) != Some("json") {
+                return Err(format!("report-schema expects json!\n{}", USAGE));
+            }
+            if args.next().is_some() {
+                return Err(format!("Too many arguments to report-schema!\n{}", USAGE));
+            }
+            return Ok(Command::ReportSchema);
+        }
+        if args.peek().and_then(|arg| arg.to_str()) == Some("replay") {
+            args.next();
+            return Replay::from_args(args).map(Command::Replay);
+        }
+        Options::from_args(args).map(|options| Command::Process(Box::new(options)))
+    }
+}
+
+impl Replay {
+    fn from_args(args: impl IntoIterator<Item = OsString>) -> Result<Self, String> {
+        let mut args = args.into_iter();
+        let (mut from_snapshot, mut expect, mut input) = (None, None, None);
+        while let Some(arg) = args.next() {
+            match arg.to_str() {
+                Some("--from-snapshot") => from_snapshot = Some(path_of(&mut args, &arg)?),
+                Some("--expect") => expect = Some(path_of(&mut args, &arg)?),
+                Some(flag) if flag.starts_with("--") => {
+                    return Err(format!("Unknown option to replay {}!\n{}", flag, USAGE))
+                }
+                _ if input.is_some() => {
+                    return Err(format!("Too many arguments to replay!\n{}", USAGE))
+                }
+                _ => input = Some(PathBuf::from(arg)),
+            }
+        }
+        let missing = |what: &str| format!("replay expects {}!\n{}", what, USAGE);
+        Ok(Replay {
+            from_snapshot: from_snapshot.ok_or_else(|| missing("--from-snapshot"))?,
+            expect: expect.ok_or_else(|| missing("--expect"))?,
+            input: input.ok_or_else(|| missing("a transaction file"))?,
+        })
+    }
+}
+
+pub struct Options {
+    /// the input CSV file of transaction records
+    pub input: PathBuf,
+    /// a report to load before processing, and then overwrite with the merged report
+    pub merge_into: Option<PathBuf>,
+    /// a snapshot to resume from
+    pub snapshot: Option<PathBuf>,
+    /// where to save a snapshot after processing
+    pub save_snapshot: Option<PathBuf>,
+    /// a snapshot to resume from, reporting only the accounts changed since
+    pub since_snapshot: Option<PathBuf>,
+    /// where to write the engine-wide sums, for reconciling against the source system
+    pub reconciliation: Option<PathBuf>,
+    /// where to write the recorded transactions after processing
+    pub dump_history: Option<PathBuf>,
+    /// ids of deposits and withdrawals applied in prior runs, to be skipped and then updated
+    pub applied_ids: Option<PathBuf>,
+    /// check the accounts against the recorded transactions after processing
+    pub audit: bool,
+    /// a file to write the report to, as well as stdout
+    pub tee: Option<PathBuf>,
+    pub format: Format,
+    pub report: ReportOptions,
+    /// exchange rates to load into the report options, for its derived totals
+    pub rates: Option<PathBuf>,
+    /// the engine's configuration, of the optional rules and how the input is read
+    pub config: Config,
+    /// check that every dispute references a transaction, before processing any
+    pub validate_references: bool,
+    /// warn of suspicious sequences of references before processing
+    pub check_sequences: bool,
+    /// where to write rejected records, with the reason for their rejection
+    pub dead_letter: Option<PathBuf>,
+    /// fail at the first rejected transaction, rather than moving on
+    pub strict: bool,
+    /// undo the transactions, rather than apply them
+    pub reverse: bool,
+    pub error_format: ErrorFormat,
+    /// print each transaction applied in canonical form, in place of the report
+    pub normalize: bool,
+    /// print the counts of transactions applied and rejected, in place of the report
+    pub count: bool,
+    /// print nothing but the report, and fatal errors
+    pub quiet: bool,
+}
+
+/// The format of the report
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+    /// JSON Lines, one account per line
+    JsonLines,
+}
+
+/// The format of a fatal error
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorFormat {
+    #[default]
+    Text,
+    /// an object of the `error` message and rejected `tx`, if any
+    Json,
+}
+
+impl Options {
+    fn from_args(args: impl IntoIterator<Item = OsString>) -> Result<Self, String> {
+        let mut args = args.into_iter();
+        let mut input = None;
+        let mut options = Options {
+            input: PathBuf::new(),
+            merge_into: None,
+            snapshot: None,
+            save_snapshot: None,
+            since_snapshot: None,
+            reconciliation: None,
+            dump_history: None,
+            tee: None,
+            applied_ids: None,
+            audit: false,
+            format: Format::Csv,
+            report: ReportOptions::default(),
+            rates: None,
+            config: Config::default(),
+            validate_references: false,
+            check_sequences: false,
+            dead_letter: None,
+            strict: false,
+            reverse: false,
+            error_format: ErrorFormat::Text,
+            normalize: false,
+            count: false,
+            quiet: false,
+        };
+        while let Some(arg) = args.next() {
+            match arg.to_str() {
+                Some("--merge-into") => options.merge_into = Some(path_of(&mut args, &arg)?),
+                Some("--snapshot") => options.snapshot = Some(path_of(&mut args, &arg)?),
+                Some("--save-snapshot") => options.save_snapshot = Some(path_of(&mut args, &arg)?),
+                Some("--since-snapshot") => {
+                    options.since_snapshot = Some(path_of(&mut args, &arg)?)
+                }
+                Some("--tee") => options.tee = Some(path_of(&mut args, &arg)?),
+                Some("--reconciliation") => {
+                    options.reconciliation = Some(path_of(&mut args, &arg)?)
+                }
+                Some("--dump-history") => options.dump_history = Some(path_of(&mut args, &arg)?),
+                Some("--applied-ids") => options.applied_ids = Some(path_of(&mut args, &arg)?),
+                Some("--audit") => options.audit = true,
+                Some("--format") => {
+                    options.format = match value_of(&mut args, &arg)?.to_str() {
+                        Some("csv") => Format::Csv,
+                        Some("json") => Format::Json,
+                        Some("jsonl") => Format::JsonLines,
+                        _ => {
+                            return Err(format!("--format must be csv, json, or jsonl!\n{}", USAGE))
+                        }
+                    }
+                }
+                Some("--pretty") => options.report.pretty = true,
+                Some("--no-total") => options.report.omit_total = true,
+                Some("--compact") => options.report.compact = true,
+                Some("--report-locked-only") => options.report.locked_only = true,
+                Some("--output-bom") => options.report.bom = true,
+                Some("--limit") => {
+                    let value = value_of(&mut args, &arg)?;
+                    let limit = value.to_str().and_then(|n| n.parse().ok()).ok_or_else(|| {
+                        format!("--limit expects a number, got {:?}!\n{}", value, USAGE)
+                    })?;
+                    options.report.limit = Some(limit);
+                }
+                Some("--flush-every") => {
+                    let value = value_of(&mut args, &arg)?;
+                    let every = value
+                        .to_str()
+                        .and_then(|n| n.parse().ok())
+                        .filter(|&every: &usize| every > 0)
+                        .ok_or_else(|| {
+                            format!(
+                                "--flush-every expects a positive number, got {:?}!\n{}",
+                                value, USAGE
+                            )
+                        })?;
+                    options.report.flush_every = Some(every);
+                }
+                Some("--max-amount") => {
+                    options.config.policy.max_amount = Some(money_of(&mut args, &arg)?)
+                }
+                Some("--alert-overdraft") => {
+                    let amount = money_of(&mut args, &arg)?;
+                    options.config.available_alert_threshold = Some(Money::ZERO - amount);
+                }
+                Some("--minimum-balance") => {
+                    options.config.policy.minimum_balance = money_of(&mut args, &arg)?
+                }
+                Some("--forbid-redispute") => options.config.policy.forbid_redispute = true,
+                Some("--allow-deposits-when-locked") => {
+                    options.config.policy.allow_deposits_when_locked = true
+                }
+                Some("--max-held") => {
+                    options.config.policy.max_held = Some(money_of(&mut args, &arg)?)
+                }
+                Some("--max-total-held") => {
+                    options.config.max_total_held = Some(money_of(&mut args, &arg)?)
+                }
+                Some("--allow-adjustments") => options.config.policy.allow_adjustments = true,
+                Some("--allow-closing") => {
+                    options.config.policy.allow_closing = true;
+                    options.report.closed = true;
+                }
+                Some("--skip-zero-amounts") => options.config.policy.skip_zero_amounts = true,
+                Some("--idempotent-duplicates") => {
+                    options.config.policy.idempotent_duplicates = true
+                }
+                Some("--no-negative") => options.config.policy.forbid_overdrawing_disputes = true,
+                Some("--reject-rounded-to-zero") => options.config.reject_rounded_to_zero = true,
+                Some("--allow-digit-separators") => options.config.allow_digit_separators = true,
+                Some("--keep-implied-accounts") => options.config.keep_implied_accounts = true,
+                Some("--strict-headers") => options.config.strict_headers = true,
+                Some("--only") => {
+                    let value = value_of(&mut args, &arg)?;
+                    let types = value
+                        .to_str()
+                        .map(|types| types.split(',').map(str::to_ascii_lowercase).collect())
+                        .filter(|types: &HashSet<String>| {
+                            types.iter().all(|t| Action::TYPES.contains(&t.as_str()))
+                        })
+                        .ok_or_else(|| {
+                            format!(
+                                "--only expects transaction types, such as deposit,withdrawal, \
+                                 got {:?}!\n{}",
+                                value, USAGE
+                            )
+                        })?;
+                    options.config.only_types = Some(types);
+                }
+                Some("--max-frac-digits") => {
+                    let value = value_of(&mut args, &arg)?;
+                    let max = value.to_str().and_then(|n| n.parse().ok()).ok_or_else(|| {
+                        format!(
+                            "--max-frac-digits expects a number, got {:?}!\n{}",
+                            value, USAGE
+                        )
+                    })?;
+                    options.config.max_fraction_digits = Some(max);
+                }
+                Some("--max-memory") => {
+                    let value = value_of(&mut args, &arg)?;
+                    let max = value.to_str().and_then(|n| n.parse().ok()).ok_or_else(|| {
+                        format!(
+                            "--max-memory expects a number of bytes, got {:?}!\n{}",
+                            value, USAGE
+                        )
+                    })?;
+                    options.config.max_memory = Some(max);
+                }
+                Some("--warn-forward-references") => options.config.warn_forward_references = true,
+                Some("--validate-references") => options.validate_references = true,
+                Some("--check-sequences") => options.check_sequences = true,
+                Some("--column-alias") => {
+                    let value = value_of(&mut args, &arg)?;
+                    let (from, to) = value
+                        .to_str()
+                        .and_then(|alias| alias.split_once('='))
+                        .ok_or_else(|| {
+                            format!(
+                                "--column-alias expects <from>=<to>, got {:?}!\n{}",
+                                value, USAGE
+                            )
+                        })?;
+                    options
+                        .config
+                        .column_aliases
+                        .insert(from.to_owned(), to.to_owned());
+                }
+                Some("--normalize") => options.normalize = true,
+                Some("--rates") => options.rates = Some(path_of(&mut args, &arg)?),
+                Some("--dead-letter") => options.dead_letter = Some(path_of(&mut args, &arg)?),
+                Some("--strict") => options.strict = true,
+                Some("--reverse") => options.reverse = true,
+                Some("--error-format") => {
+                    options.error_format = match value_of(&mut args, &arg)?.to_str() {
+                        Some("text") => ErrorFormat::Text,
+                        Some("json") => ErrorFormat::Json,
+                        _ => {
+                            return Err(format!("--error-format must be text or json!\n{}", USAGE))
+                        }
+                    }
+                }
+                Some("--count") => options.count = true,
+                Some("--quiet") => options.quiet = true,
+                Some("--currency") => {
+                    let value = value_of(&mut args, &arg)?;
+                    let currency = value
+                        .to_str()
+                        .filter(|code| {
+                            code.len() == 3 && code.bytes().all(|b| b.is_ascii_alphabetic())
+                        })
+                        .ok_or_else(|| {
+                            format!(
+                                "--currency expects a currency code, such as JPY, got {:?}!\n{}",
+                                value, USAGE
+                            )
+                        })?
+                        .to_ascii_uppercase();
+                    options.config.currency = Some(currency.clone());
+                    options.report.currency = Some(currency);
+                }
+                Some("--hash-seed") => {
+                    let value = value_of(&mut args, &arg)?;
+                    let seed = value.to_str().and_then(|n| n.parse().ok()).ok_or_else(|| {
+                        format!("--hash-seed expects a number, got {:?}!\n{}", value, USAGE)
+                    })?;
+                    options.config.hash_seed = Some(seed);
+                }
+                Some("--comment") => options.config.comment = Some(byte_of(&mut args, &arg)?),
+                Some("--delimiter") => options.config.delimiter = byte_of(&mut args, &arg)?,
+                Some("--decimal-sep") => {
+                    options.config.decimal_separator = char::from(byte_of(&mut args, &arg)?)
+                }
+                Some("--trim") => {
+                    options.config.trim = match value_of(&mut args, &arg)?.to_str() {
+                        Some("none") => csv::Trim::None,
+                        Some("headers") => csv::Trim::Headers,
+                        Some("fields") => csv::Trim::Fields,
+                        Some("all") => csv::Trim::All,
+                        _ => {
+                            return Err(format!(
+                                "--trim must be none, headers, fields, or all!\n{}",
+                                USAGE
+                            ))
+                        }
+                    }
+                }
+                Some(flag) if flag.starts_with("--") => {
+                    return Err(format!("Unknown option {}!\n{}", flag, USAGE))
+                }
+                _ if input.is_some() => {
+                    return Err(format!(
+                        "Too many arguments! Expected one argument, the input CSV file.\n{}",
+                        USAGE
+                    ))
+                }
+                _ => input = Some(PathBuf::from(arg)),
+            }
+        }
+        options.input = input.ok_or_else(|| {
+            format!(
+                "Too few arguments! Expected one argument, the input CSV file.\n{}",
+                USAGE
+            )
+        })?;
+        if options.report.pretty && options.format != Format::Json {
+            return Err(format!("--pretty requires --format json!\n{}", USAGE));
+        }
+        if options.report.flush_every.is_some() && options.format != Format::Csv {
+            return Err(format!("--flush-every requires --format csv!\n{}", USAGE));
+        }
+        if options.report.bom && options.format != Format::Csv {
+            return Err(format!("--output-bom requires --format csv!\n{}", USAGE));
+        }
+        if options.merge_into.is_some() && options.format != Format::Csv {
+            return Err(format!("--merge-into requires --format csv!\n{}", USAGE));
+        }
+        if options.count
+            && (options.normalize
+                || options.merge_into.is_some()
+                || options.tee.is_some()
+                || options.format != Format::Csv)
+        {
+            // there's no report to print, nor to merge or copy
+            return Err(format!(
+                "--count can't be used with --normalize, --merge-into, --tee, or a JSON --format!\n{}",
+                USAGE
+            ));
+        }
+        if options.normalize && (options.merge_into.is_some() || options.format != Format::Csv) {
+            // there's no report to merge, nor to write as JSON
+            return Err(format!(
+                "--normalize can't be used with --merge-into or a JSON --format!\n{}",
+                USAGE
+            ));
+        }
+        if options.config.decimal_separator == char::from(options.config.delimiter) {
+            return Err(format!(
+                "--decimal-sep must differ from the --delimiter!\n{}",
+                USAGE
+            ));
+        }
+        if options.strict && (options.normalize || options.dead_letter.is_some()) {
+            // nothing is ever rejected without failing
+            return Err(format!(
+                "--strict can't be used with --normalize or --dead-letter!\n{}",
+                USAGE
+            ));
+        }
+        if options.reverse && options.snapshot.is_none() && options.since_snapshot.is_none() {
+            // there'd be no transactions to undo
+            return Err(format!(
+                "--reverse requires --snapshot or --since-snapshot!\n{}",
+                USAGE
+            ));
+        }
+        if options.reverse
+            && (options.strict
+                || options.normalize
+                || options.dead_letter.is_some()
+                || options.count)
+        {
+            // they describe transactions applied, not undone
+            return Err(format!(
+                "--reverse can't be used with --strict, --normalize, --dead-letter, or --count!\n{}",
+                USAGE
+            ));
+        }
+        if options.normalize && options.dead_letter.is_some() {
+            return Err(format!(
+                "--normalize can't be used with --dead-letter!\n{}",
+                USAGE
+            ));
+        }
+        if options.merge_into.is_some() && options.tee.is_some() {
+            // the report already goes to the merged file, rather than stdout
+            return Err(format!("--merge-into can't be used with --tee!\n{}", USAGE));
+        }
+        if options.merge_into.is_some() && options.report.omit_total {
+            // a report without totals can't be merged into again
+            return Err(format!(
+                "--merge-into can't be used with --no-total!\n{}",
+                USAGE
+            ));
+        }
+        if options.merge_into.is_some() && options.report.limit.is_some() {
+            // that'd drop the rest of the accounts from the merged report
+            return Err(format!(
+                "--merge-into can't be used with --limit!\n{}",
+                USAGE
+            ));
+        }
+        if options.merge_into.is_some() && options.report.locked_only {
+            // as would this
+            return Err(format!(
+                "--merge-into can't be used with --report-locked-only!\n{}",
+                USAGE
+            ));
+        }
+        if options.merge_into.is_some() && options.since_snapshot.is_some() {
+            // and this
+            return Err(format!(
+                "--merge-into can't be used with --since-snapshot!\n{}",
+                USAGE
+            ));
+        }
+        if options.rates.is_some() && options.report.currency.is_none() {
+            // there'd be no currency to convert from
+            return Err(format!("--rates requires --currency!\n{}", USAGE));
+        }
+        if options.merge_into.is_some() && options.rates.is_some() {
+            // the derived totals would be stale once merged into
+            return Err(format!(
+                "--merge-into can't be used with --rates!\n{}",
+                USAGE
+            ));
+        }
+        if options.snapshot.is_some() && options.since_snapshot.is_some() {
+            return Err(format!(
+                "--snapshot can't be used with --since-snapshot!\n{}",
+                USAGE
+            ));
+        }
+        Ok(options)
+    }
+}
+
+/// Takes the value following `flag`
+fn value_of(
+    args: &mut impl Iterator<Item = OsString>,
+    flag: &OsString,
+) -> Result<OsString, String> {
+    args.next()
+        .ok_or_else(|| format!("Missing value for {}!\n{}", flag.to_string_lossy(), USAGE))
+}
+
+/// Takes the single ASCII character following `flag`
+fn byte_of(args: &mut impl Iterator<Item = OsString>, flag: &OsString) -> Result<u8, String> {
+    let value = value_of(args, flag)?;
+    match value.as_encoded_bytes() {
+        &[byte] if byte.is_ascii() => Ok(byte),
+        _ => Err(format!(
+            "{} expects a single ASCII character, got {:?}!\n{}",
+            flag.to_string_lossy(),
+            value,
+            USAGE
+        )),
+    }
+}
+
+/// Takes the path following `flag`
+fn path_of(args: &mut impl Iterator<Item = OsString>, flag: &OsString) -> Result<PathBuf, String> {
+    value_of(args, flag).map(PathBuf::from)
+}
+
+/// Takes the non-negative amount of money following `flag`
+fn money_of(args: &mut impl Iterator<Item = OsString>, flag: &OsString) -> Result<Money, String> {
+    let value = value_of(args, flag)?;
+    let invalid = |reason: &dyn std::fmt::Display| {
+        format!(
+            "{} expects an amount of money, got {:?} ({})!\n{}",
+            flag.to_string_lossy(),
+            value,
+            reason,
+            USAGE
+        )
+    };
+    let money: Money = value
+        .to_str()
+        .ok_or_else(|| invalid(&"not unicode"))?
+        .parse()
+        .map_err(|e| invalid(&e))?;
+    if money.is_negative() {
+        return Err(invalid(&"negative"));
+    }
+    Ok(money)
+}
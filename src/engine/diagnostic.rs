@@ -0,0 +1,108 @@
+//! `Diagnostic`s are problems noticed by the [`PaymentEngine`](super::PaymentEngine)
+//! that are worth reporting, but don't stop processing.
+//!
+use crate::account::{Client, Money, TxId};
+use std::fmt::Display;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// A client holds more funds than their recorded deposits less withdrawals
+    FundsExceedRecorded {
+        client: Client,
+        total: Money,
+        recorded: Money,
+    },
+    /// A dispute, resolve, or chargeback referenced an id higher than any deposit
+    /// or withdrawal seen before it
+    ForwardReference(TxId),
+    /// A dispute drove a client's available funds below the configured alert threshold
+    AvailableBelowThreshold {
+        client: Client,
+        tx: TxId,
+        available: Money,
+    },
+    /// The dispute, resolve, or chargeback on `line` references an id that's neither recorded
+    /// nor that of any deposit or withdrawal in the input
+    DanglingReference { line: u64, tx: TxId },
+    /// The dispute, resolve, or chargeback on `line` repeats the last reference to `tx`,
+    /// as in a resolve straight after another resolve, so the repeat would be rejected
+    RepeatedReference {
+        line: u64,
+        tx: TxId,
+        action: &'static str,
+    },
+    /// The dispute, resolve, or chargeback on `line` references an id that's neither recorded
+    /// nor that of any deposit or withdrawal earlier in the input
+    ReferenceBeforeTransaction { line: u64, tx: TxId },
+    /// The withdrawal on `line` was rejected under
+    /// [`Config::reject_rounded_to_zero`](super::Config::reject_rounded_to_zero), as its
+    /// `written` amount rounds to zero at `scale` decimal places
+    RoundedToZero {
+        line: u64,
+        tx: TxId,
+        written: String,
+        scale: usize,
+    },
+    /// Processing was stopped early, leaving the record on `line` and those after it unapplied
+    Interrupted { line: u64 },
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diagnostic::FundsExceedRecorded {
+                client,
+                total,
+                recorded,
+            } => write!(
+                f,
+                "client {}'s total funds {} exceed their recorded deposits less withdrawals {}",
+                client, total, recorded
+            ),
+            Diagnostic::ForwardReference(tx) => write!(
+                f,
+                "transaction {} was referenced before any deposit or withdrawal with that id",
+                tx
+            ),
+            Diagnostic::AvailableBelowThreshold {
+                client,
+                tx,
+                available,
+            } => write!(
+                f,
+                "dispute of transaction {} left client {}'s available funds at {}, below the alert threshold",
+                tx, client, available
+            ),
+            Diagnostic::DanglingReference { line, tx } => write!(
+                f,
+                "line {} references transaction {}, which no deposit or withdrawal has",
+                line, tx
+            ),
+            Diagnostic::RepeatedReference { line, tx, action } => write!(
+                f,
+                "line {} repeats the {} of transaction {} straight after another",
+                line, action, tx
+            ),
+            Diagnostic::ReferenceBeforeTransaction { line, tx } => write!(
+                f,
+                "line {} references transaction {} before any deposit or withdrawal with that id",
+                line, tx
+            ),
+            Diagnostic::RoundedToZero {
+                line,
+                tx,
+                written,
+                scale,
+            } => write!(
+                f,
+                "line {} withdrawal {} of {:?} rounds to zero at {} decimal places, so was rejected",
+                line, tx, written, scale
+            ),
+            Diagnostic::Interrupted { line } => write!(
+                f,
+                "interrupted, records from line {} onwards were not processed",
+                line
+            ),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+//! Errors combining the [`PaymentEngine`](super::PaymentEngine)s of independently processed shards
+//!
+use crate::account::{Client, TxId};
+use std::fmt::Display;
+
+/// Why two shards can't be merged, having processed overlapping input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeError {
+    /// both shards have an account for the client
+    SharedClient(Client),
+    /// both shards have recorded a transaction with the id
+    SharedTx(TxId),
+}
+
+impl Display for MergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeError::SharedClient(client) => {
+                write!(f, "client {} appears in both shards", client)
+            }
+            MergeError::SharedTx(tx) => write!(f, "transaction {} appears in both shards", tx),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
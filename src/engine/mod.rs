@@ -0,0 +1,1538 @@
+//! `PaymentEngine` owns every [`Account`] along with the shared [`TxHistory`],
+//! and reads transactions from and writes reports to CSV.
+//!
+use crate::account::{
+    self, currency, Account, Action, ActionHandler, Client, CompletedTx, CompletedTxKind,
+    HashState, Money, Policy, Transaction, TxHistory, TxId,
+};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+mod diagnostic;
+mod merge;
+mod reconciliation;
+mod report;
+mod reverse;
+mod snapshot;
+mod stats;
+mod strict;
+#[cfg(test)]
+mod tests;
+
+pub use diagnostic::Diagnostic;
+pub use merge::MergeError;
+pub use reconciliation::Reconciliation;
+pub use report::{AccountReport, ReportOptions};
+pub use reverse::ReverseError;
+pub use snapshot::{Snapshot, SnapshotError, Violation};
+pub use stats::Stats;
+pub use strict::StrictError;
+
+#[derive(Default)]
+pub struct PaymentEngine {
+    accounts: HashMap<Client, Account, HashState>,
+    tx_history: TxHistory,
+    config: Config,
+    /// the highest deposit or withdrawal id seen so far
+    highest_tx_id: Option<TxId>,
+    diagnostics: Vec<Diagnostic>,
+    /// the results of transactions submitted with an idempotency key
+    keyed_results: HashMap<String, Result<(), account::Error>>,
+    /// ids of deposits and withdrawals applied in prior runs, which are skipped
+    previously_applied: HashSet<TxId>,
+    /// the sums of transactions applied, though not yet of balances
+    totals: Reconciliation,
+    stats: Stats,
+    /// the sum of every account's held funds, kept for [`Config::max_total_held`]
+    total_held: Money,
+    /// the handlers of custom transaction types, by their `type`
+    handlers: HashMap<&'static str, Box<dyn ActionHandler>>,
+}
+
+// the size of an entry in the tables of accounts and history, with a byte of hash table metadata
+const ACCOUNT_ENTRY_SIZE: usize = std::mem::size_of::<(Client, Account)>() + 1;
+const HISTORY_ENTRY_SIZE: usize = std::mem::size_of::<(TxId, CompletedTx)>() + 1;
+
+/// A transaction that is currently in dispute
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpenDispute {
+    pub tx: TxId,
+    pub client: Client,
+    /// the disputed amount, currently held
+    pub amount: Money,
+}
+
+/// The default config enforces none of the optional rules, and emits no optional diagnostics.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// rules enforced on every transaction
+    pub policy: Policy,
+    /// warn of disputes, resolves, and chargebacks referencing an id higher than
+    /// any deposit or withdrawal seen so far, likely a sign of misordered records
+    pub warn_forward_references: bool,
+    /// warn when a dispute drives an account's available funds below this (typically negative)
+    /// threshold, though the dispute is still applied
+    pub available_alert_threshold: Option<Money>,
+    /// renames the input's columns, from the upstream's names to `type`, `client`, `tx`, and `amount`
+    pub column_aliases: HashMap<String, String>,
+    /// which whitespace to trim from the input, by default both headers and fields.
+    /// [`Money`] doesn't tolerate whitespace, so trimming fields is necessary for padded amounts.
+    pub trim: csv::Trim,
+    /// the byte separating the fields of the input, `,` by default
+    pub delimiter: u8,
+    /// the decimal point of the input's amounts, `.` by default, or `,` as in some locales
+    /// (along with a delimiter other than `,`). Reports are always written with `.`.
+    pub decimal_separator: char,
+    /// the currency of the input's amounts, such as `JPY`, which are rounded to its
+    /// [scale](currency::scale_of) as they're read. By default they're kept to [`Money::SCALE`]
+    /// decimal places, as are those of any currency without fewer.
+    pub currency: Option<String>,
+    /// lines of the input starting with this byte, such as `#`, are skipped as comments.
+    /// There's no comment byte by default.
+    pub comment: Option<u8>,
+    /// seeds the hashing of accounts and transaction ids, so that iterating over them is in
+    /// the same order every run, such as for debugging. They're randomly seeded by default.
+    pub hash_seed: Option<u64>,
+    /// reject withdrawals that round to zero at [`Money::SCALE`] decimal places, such as
+    /// `0.00004`, or at the `currency`'s scale, as they're read, rather than applying them as
+    /// withdrawals of nothing
+    pub reject_rounded_to_zero: bool,
+    /// fail reading amounts with more than this many fractional digits, as likely corrupt,
+    /// rather than rounding them to [`Money::SCALE`] decimal places. Unbounded by default.
+    pub max_fraction_digits: Option<usize>,
+    /// keep the empty account opened for a client whose first transaction, a rejected dispute,
+    /// resolve, or chargeback, only implied it. Such accounts are left out by default.
+    pub keep_implied_accounts: bool,
+    /// fail processing of CSV before the [estimated](PaymentEngine::estimated_memory) memory
+    /// use of the accounts and history would exceed this many bytes, rather than risk running
+    /// out of memory. Unbounded by default.
+    pub max_memory: Option<usize>,
+    /// fail reading an input whose header, once renamed by the `column_aliases`, isn't exactly
+    /// `type`, `client`, `tx`, and `amount` in any order, rather than ignoring extra columns
+    /// and reading records without an `amount` column as short
+    pub strict_headers: bool,
+    /// apply only transactions of these [types](Action::type_name), such as `deposit` and
+    /// `withdrawal`, skipping any others without effect. Every type is applied by default.
+    pub only_types: Option<HashSet<String>>,
+    /// reject disputes that would hold more than this in total across every account, as
+    /// [`account::Error::GlobalHeldLimitExceeded`], leaving the account untouched
+    pub max_total_held: Option<Money>,
+    /// strip underscores from amounts before parsing them, accepting digit separators as in
+    /// `1_000.50`, such as leak into some generated exports. They're rejected by default.
+    pub allow_digit_separators: bool,
+    /// once set, such as by a Ctrl-C handler, processing of CSV stops before the next record,
+    /// leaving the accounts as they were after the last record applied
+    pub stop: Option<Arc<AtomicBool>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            policy: Policy::default(),
+            warn_forward_references: false,
+            available_alert_threshold: None,
+            column_aliases: HashMap::new(),
+            trim: csv::Trim::All,
+            delimiter: b',',
+            decimal_separator: '.',
+            currency: None,
+            comment: None,
+            hash_seed: None,
+            reject_rounded_to_zero: false,
+            max_fraction_digits: None,
+            keep_implied_accounts: false,
+            max_memory: None,
+            strict_headers: false,
+            only_types: None,
+            max_total_held: None,
+            allow_digit_separators: false,
+            stop: None,
+        }
+    }
+}
+
+/// The CSV reader configuration used for both transaction records and reports,
+/// though the trimming of transaction records is configurable
+pub fn csv_reader_builder() -> csv::ReaderBuilder {
+    let mut builder = csv::ReaderBuilder::new();
+    builder
+        .has_headers(true)
+        .flexible(true)
+        .trim(csv::Trim::All);
+    builder
+}
+
+/// A reader that remembers the last byte read, to tell whether the input ended with a newline
+struct LastByte<R> {
+    inner: R,
+    last: Option<u8>,
+}
+
+impl<R> LastByte<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, last: None }
+    }
+}
+
+impl<R: io::Read> io::Read for LastByte<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if read > 0 {
+            self.last = Some(buf[read - 1]);
+        }
+        Ok(read)
+    }
+}
+
+/// Reads transaction records, as configured by a [`Config`]
+struct TransactionReader<R> {
+    csv_in: csv::Reader<LastByte<R>>,
+    /// the input's headers, renamed by the column aliases
+    headers: csv::StringRecord,
+    /// the record last read
+    record: csv::StringRecord,
+    /// the column of amounts, if any
+    amount_column: Option<usize>,
+    decimal_separator: char,
+    /// the decimal places amounts are rounded to, those of the configured currency
+    scale: usize,
+    reject_rounded_to_zero: bool,
+    /// the amount of the withdrawal last read as written, if it rounds to zero and
+    /// `reject_rounded_to_zero` is set
+    rounded_to_zero: Option<String>,
+    max_fraction_digits: Option<usize>,
+    allow_digit_separators: bool,
+    /// the types of custom transactions, which have handlers
+    custom_types: Vec<&'static str>,
+}
+
+impl<R: io::Read> TransactionReader<R> {
+    fn new(
+        reader: R,
+        config: &Config,
+        custom_types: Vec<&'static str>,
+    ) -> Result<Self, csv::Error> {
+        let mut csv_in = csv_reader_builder()
+            .trim(config.trim)
+            .delimiter(config.delimiter)
+            .comment(config.comment)
+            .from_reader(LastByte::new(reader));
+        if !config.column_aliases.is_empty() {
+            let headers = csv_in
+                .headers()?
+                .iter()
+                .map(|header| {
+                    config
+                        .column_aliases
+                        .get(header)
+                        .map_or(header, String::as_str)
+                })
+                .collect();
+            csv_in.set_headers(headers);
+        }
+        let headers = csv_in.headers()?.clone();
+        unique_headers(&headers)?;
+        if config.strict_headers {
+            exact_headers(&headers)?;
+        }
+        let amount_column = headers.iter().position(|header| header == "amount");
+        Ok(Self {
+            csv_in,
+            headers,
+            record: csv::StringRecord::new(),
+            amount_column,
+            decimal_separator: config.decimal_separator,
+            scale: config
+                .currency
+                .as_deref()
+                .map_or(Money::SCALE, currency::scale_of),
+            reject_rounded_to_zero: config.reject_rounded_to_zero,
+            rounded_to_zero: None,
+            max_fraction_digits: config.max_fraction_digits,
+            allow_digit_separators: config.allow_digit_separators,
+            custom_types,
+        })
+    }
+
+    /// Reads the next record, returning `false` at the end of the input
+    fn read_record(&mut self) -> Result<bool, csv::Error> {
+        self.csv_in.read_record(&mut self.record)
+    }
+
+    /// The line of the record last read
+    fn line(&self) -> u64 {
+        self.record.position().map_or(0, csv::Position::line)
+    }
+
+    /// Deserializes the record last read
+    fn transaction(&mut self) -> Result<Transaction, csv::Error> {
+        // as written, before rewriting its decimal separator rounds it
+        let written_amount = self
+            .amount_column
+            .filter(|_| self.reject_rounded_to_zero)
+            .and_then(|column| self.record.get(column))
+            .map(str::to_owned);
+        if let Some(column) = self.amount_column.filter(|_| self.allow_digit_separators) {
+            without_digit_separators(&mut self.record, column);
+        }
+        if let (Some(column), Some(max)) = (self.amount_column, self.max_fraction_digits) {
+            within_fraction_digits(&self.record, column, self.decimal_separator, max)?;
+        }
+        if let Some(column) = self
+            .amount_column
+            .filter(|_| self.decimal_separator != '.' || self.scale < Money::SCALE)
+        {
+            with_decimal_point(&mut self.record, column, self.decimal_separator, self.scale)?;
+        }
+        let tx = match self.record.deserialize(Some(&self.headers)) {
+            Ok(tx) => tx,
+            // the built in types take precedence over any custom type of the same name
+            Err(error) => match self.custom_transaction() {
+                Some(tx) => tx?,
+                None => return Err(truncation_or(error, &mut self.csv_in)?),
+            },
+        };
+        // for the engine to reject, while reading on
+        self.rounded_to_zero = match (tx.action(), written_amount) {
+            (Action::Withdrawal { amount }, Some(written))
+                if amount.is_zero() && written.bytes().any(|b| matches!(b, b'1'..=b'9')) =>
+            {
+                Some(written)
+            }
+            _ => None,
+        };
+        Ok(tx)
+    }
+
+    /// Deserializes the record last read as an [`Action::Custom`], if its type is custom
+    fn custom_transaction(&self) -> Option<Result<Transaction, csv::Error>> {
+        #[derive(serde::Deserialize)]
+        struct CustomRecord {
+            client: Client,
+            #[serde(rename = "tx")]
+            id: TxId,
+            amount: Option<Money>,
+        }
+        let column = self.headers.iter().position(|header| header == "type")?;
+        let action_type = self.record.get(column)?;
+        let name = *self
+            .custom_types
+            .iter()
+            .find(|name| name.eq_ignore_ascii_case(action_type))?;
+        Some(self.record.deserialize(Some(&self.headers)).map(
+            |CustomRecord { client, id, amount }| {
+                Transaction::new(Action::Custom { name, amount }, client, id)
+            },
+        ))
+    }
+}
+
+/// Explains a record's deserialization `error` as truncation of the input, such as by an
+/// interrupted download, when the record is the last and isn't terminated by a newline.
+fn truncation_or<R: io::Read>(
+    error: csv::Error,
+    csv_in: &mut csv::Reader<LastByte<R>>,
+) -> Result<csv::Error, csv::Error> {
+    let is_last = !csv_in.read_record(&mut csv::StringRecord::new())?;
+    if !is_last || matches!(csv_in.get_ref().last, Some(b'\n' | b'\r')) {
+        return Ok(error);
+    }
+    let line = error.position().map_or(0, csv::Position::line);
+    Ok(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        format!(
+            "input is truncated, the last record (line {}) is incomplete: {}",
+            line, error
+        ),
+    )
+    .into())
+}
+
+/// Rewrites the amount in `record`'s `column` from one with the decimal `separator` to
+/// one with a decimal point, as [`Money`]'s deserializer expects, rounded once to `scale`
+/// decimal places.
+fn with_decimal_point(
+    record: &mut csv::StringRecord,
+    column: usize,
+    separator: char,
+    scale: usize,
+) -> Result<(), csv::Error> {
+    // such as the amount of a dispute
+    let Some(amount) = record.get(column).filter(|amount| !amount.is_empty()) else {
+        return Ok(());
+    };
+    let money = Money::parse(amount, separator, scale).map_err(|e| {
+        let line = record.position().map_or(0, csv::Position::line);
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("line {}: {} in money field: {:?}", line, e, amount),
+        )
+    })?;
+    set_field(record, column, &format!("{:.*}", Money::SCALE, money));
+    Ok(())
+}
+
+/// Strips underscores separating the digits of the amount in `record`'s `column`, as in
+/// `1_000.50`, which [`Money`]'s deserializer otherwise rejects as invalid digits.
+fn without_digit_separators(record: &mut csv::StringRecord, column: usize) {
+    if let Some(amount) = record.get(column).filter(|amount| amount.contains('_')) {
+        let amount = amount.replace('_', "");
+        set_field(record, column, &amount);
+    }
+}
+
+/// Replaces the field in `record`'s `column` with `value`, keeping the record's position
+fn set_field(record: &mut csv::StringRecord, column: usize, value: &str) {
+    let position = record.position().cloned();
+    *record = record
+        .iter()
+        .enumerate()
+        .map(|(i, field)| if i == column { value } else { field })
+        .collect();
+    record.set_position(position);
+}
+
+/// Rejects an amount in `record`'s `column` with more than `max` digits after the decimal
+/// `separator`, before they're rounded away.
+fn within_fraction_digits(
+    record: &csv::StringRecord,
+    column: usize,
+    separator: char,
+    max: usize,
+) -> Result<(), csv::Error> {
+    let Some(amount) = record.get(column) else {
+        return Ok(());
+    };
+    let digits = amount
+        .split_once(separator)
+        .map_or(0, |(_, fraction)| fraction.len());
+    if digits <= max {
+        return Ok(());
+    }
+    let line = record.position().map_or(0, csv::Position::line);
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "line {}: {} fractional digits, more than the maximum of {}, in money field: {:?}",
+            line, digits, max, amount
+        ),
+    )
+    .into())
+}
+
+/// Rejects `headers` that name a column more than once, as serde would otherwise
+/// silently take the last of the repeated columns it doesn't know.
+fn unique_headers(headers: &csv::StringRecord) -> Result<(), csv::Error> {
+    for (i, header) in headers.iter().enumerate() {
+        if let Some(repeat) = headers.iter().skip(i + 1).position(|h| h == header) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "duplicate column {:?} in the header (columns {} and {})",
+                    header,
+                    i + 1,
+                    i + repeat + 2
+                ),
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Rejects `headers` missing any of the columns of a transaction record, or with any other
+fn exact_headers(headers: &csv::StringRecord) -> Result<(), csv::Error> {
+    const COLUMNS: [&str; 4] = ["type", "client", "tx", "amount"];
+    let missing = COLUMNS.iter().find(|&&c| !headers.iter().any(|h| h == c));
+    let problem = if let Some(missing) = missing {
+        format!("missing column {:?} in the header", missing)
+    } else if let Some(i) = headers.iter().position(|h| !COLUMNS.contains(&h)) {
+        format!(
+            "unexpected column {:?} in the header (column {})",
+            &headers[i],
+            i + 1
+        )
+    } else {
+        return Ok(());
+    };
+    Err(io::Error::new(io::ErrorKind::InvalidData, problem).into())
+}
+
+impl PaymentEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_config(config: Config) -> Self {
+        let hash_state = config
+            .hash_seed
+            .map_or_else(HashState::default, HashState::Seeded);
+        Self {
+            accounts: HashMap::with_hasher(hash_state.clone()),
+            tx_history: TxHistory::with_hasher(hash_state),
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Registers `handler` to apply transactions whose `type` is `name`, matched regardless of
+    /// case, replacing any handler already registered for it. The built in types can't be
+    /// overridden.
+    ///
+    /// Custom transactions aren't included in the [`PaymentEngine::reconciliation`]'s sums.
+    pub fn register_action(&mut self, name: &'static str, handler: impl ActionHandler + 'static) {
+        self.handlers.insert(name, Box::new(handler));
+    }
+
+    fn custom_types(&self) -> Vec<&'static str> {
+        self.handlers.keys().copied().collect()
+    }
+
+    /// Applies `tx` to its client's account, opening the account if it's new.
+    ///
+    /// Deposits and withdrawals already applied in a prior run,
+    /// per [`PaymentEngine::load_applied_ids`], are silently skipped,
+    /// as are transactions of any type left out of [`Config::only_types`].
+    pub fn process_transaction(&mut self, tx: &Transaction) -> Result<(), account::Error> {
+        self.stats.read += 1;
+        if let Some(only) = &self.config.only_types {
+            if !only.contains(tx.action().type_name()) {
+                self.stats.skipped += 1;
+                return Ok(());
+            }
+        }
+        match tx.action() {
+            Action::Deposit { .. }
+            | Action::Withdrawal { .. }
+            | Action::WithdrawAll
+            | Action::Adjust { .. }
+            | Action::Custom { .. } => {
+                self.highest_tx_id = self.highest_tx_id.max(Some(tx.id()));
+                if self.previously_applied.contains(&tx.id()) {
+                    self.stats.skipped += 1;
+                    return Ok(());
+                }
+            }
+            Action::Dispute
+            | Action::PartialDispute { .. }
+            | Action::Resolve
+            | Action::Chargeback => {
+                if self.config.warn_forward_references && Some(tx.id()) > self.highest_tx_id {
+                    self.diagnostics.push(Diagnostic::ForwardReference(tx.id()));
+                }
+            }
+            // neither recorded nor referencing a recorded transaction
+            Action::Close => (),
+        }
+        let client = tx.client();
+        let opened = !self.accounts.contains_key(&client);
+        let account = self
+            .accounts
+            .entry(client)
+            .or_insert_with(|| Account::new(client));
+        let available_before = account.available();
+        let held_before = account.held();
+        // to put back, should the dispute hold too much in total
+        let held_limit = match (self.config.max_total_held, tx.action()) {
+            (Some(max), Action::Dispute | Action::PartialDispute { .. }) => {
+                Some((max, account.clone(), self.tx_history.get(tx.id()).cloned()))
+            }
+            _ => None,
+        };
+        // accepted without being applied again, if it's accepted at all
+        let replayed = self.config.policy.idempotent_duplicates
+            && matches!(
+                tx.action(),
+                Action::Deposit { .. }
+                    | Action::Withdrawal { .. }
+                    | Action::WithdrawAll
+                    | Action::Adjust { .. }
+            )
+            && self.tx_history.contains(tx.id());
+        let result = match tx.action() {
+            Action::Custom { name, .. } => match self.handlers.get(name) {
+                Some(handler) => handler.apply(account, tx, &mut self.tx_history),
+                None => Err(account::Error::UnhandledAction(tx.id())),
+            },
+            _ => account.process_transaction_with(tx, &mut self.tx_history, &self.config.policy),
+        };
+        let result = match (result, held_limit) {
+            (Ok(()), Some((max, before, recorded)))
+                if self.total_held + (account.held() - held_before) > max =>
+            {
+                *account = before;
+                if let Some(recorded) = recorded {
+                    self.tx_history.restore(tx.id(), recorded);
+                }
+                Err(account::Error::GlobalHeldLimitExceeded(tx.id()))
+            }
+            (result, _) => result,
+        };
+        self.total_held += account.held() - held_before;
+        if let Err(error) = result {
+            let implied = matches!(
+                tx.action(),
+                Action::Dispute
+                    | Action::PartialDispute { .. }
+                    | Action::Resolve
+                    | Action::Chargeback
+            );
+            if opened && implied && !self.config.keep_implied_accounts {
+                self.accounts.remove(&client);
+            }
+            *self.stats.rejected.entry(error.name()).or_default() += 1;
+            return Err(error);
+        }
+        if replayed {
+            self.stats.skipped += 1;
+            return Ok(());
+        }
+        self.stats.applied += 1;
+        match tx.action() {
+            Action::Deposit { amount } => self.totals.deposits += amount,
+            Action::Withdrawal { amount } => self.totals.withdrawals += amount,
+            Action::WithdrawAll => {
+                self.totals.withdrawals += available_before - account.available()
+            }
+            Action::Adjust { amount } => self.totals.adjustments += amount,
+            Action::Chargeback => self.totals.charged_back += held_before - account.held(),
+            Action::Dispute | Action::PartialDispute { .. } => {
+                let available = account.available();
+                if let Some(threshold) = self.config.available_alert_threshold {
+                    if available < threshold {
+                        self.diagnostics.push(Diagnostic::AvailableBelowThreshold {
+                            client,
+                            tx: tx.id(),
+                            available,
+                        });
+                    }
+                }
+            }
+            // closing moves no funds, and custom actions are opaque, so can't be accounted for
+            Action::Resolve | Action::Close | Action::Custom { .. } => (),
+        }
+        Ok(())
+    }
+
+    /// The report of `tx`'s client's account as it'd be after applying `tx`, or the reason
+    /// it'd be rejected, as by [`PaymentEngine::process_transaction`], but without changing
+    /// anything, such as to pre-check the effect of a dispute.
+    ///
+    /// Custom actions are simulated on a copy of the whole history, as their handlers need one.
+    pub fn simulate(&self, tx: &Transaction) -> Result<AccountReport, account::Error> {
+        let client = tx.client();
+        let mut account = self
+            .accounts
+            .get(&client)
+            .cloned()
+            .unwrap_or_else(|| Account::new(client));
+        let excluded = self
+            .config
+            .only_types
+            .as_ref()
+            .is_some_and(|only| !only.contains(tx.action().type_name()));
+        let skipped = excluded
+            || match tx.action() {
+                Action::Deposit { .. }
+                | Action::Withdrawal { .. }
+                | Action::WithdrawAll
+                | Action::Adjust { .. }
+                | Action::Custom { .. } => self.previously_applied.contains(&tx.id()),
+                Action::Dispute
+                | Action::PartialDispute { .. }
+                | Action::Resolve
+                | Action::Chargeback
+                | Action::Close => false,
+            };
+        if !skipped {
+            match tx.action() {
+                Action::Custom { name, .. } => {
+                    let handler = self
+                        .handlers
+                        .get(name)
+                        .ok_or(account::Error::UnhandledAction(tx.id()))?;
+                    handler.apply(&mut account, tx, &mut self.tx_history.clone())?;
+                }
+                _ => account.process_transaction_with(
+                    tx,
+                    &mut account::Overlay::new(&self.tx_history),
+                    &self.config.policy,
+                )?,
+            }
+            let held_before = self
+                .accounts
+                .get(&client)
+                .map_or(Money::ZERO, Account::held);
+            let disputed = matches!(tx.action(), Action::Dispute | Action::PartialDispute { .. });
+            if disputed
+                && self
+                    .config
+                    .max_total_held
+                    .is_some_and(|max| self.total_held + (account.held() - held_before) > max)
+            {
+                return Err(account::Error::GlobalHeldLimitExceeded(tx.id()));
+            }
+        }
+        Ok(AccountReport::from(&account))
+    }
+
+    /// Applies `tx` like [`PaymentEngine::process_transaction`], unless a transaction
+    /// has already been submitted with the same idempotency `key`, in which case
+    /// nothing is applied and the earlier transaction's result is returned again.
+    ///
+    /// This allows clients to safely retry submissions, unlike resubmitting the
+    /// same transaction id which is rejected as a duplicate.
+    pub fn process_keyed_transaction(
+        &mut self,
+        key: &str,
+        tx: &Transaction,
+    ) -> Result<(), account::Error> {
+        if let Some(result) = self.keyed_results.get(key) {
+            return *result;
+        }
+        let result = self.process_transaction(tx);
+        self.keyed_results.insert(key.to_owned(), result);
+        result
+    }
+
+    /// Applies every transaction in `batch`, in order, or none of them at all, such as
+    /// the withdrawal and deposit of a transfer between two accounts.
+    ///
+    /// At the first rejected transaction, the accounts and history are rolled back to
+    /// as they were before the batch, and the rejection is returned.
+    pub fn process_batch(&mut self, batch: &[Transaction]) -> Result<(), account::Error> {
+        // everything the batch may touch, as it was before
+        let accounts: HashMap<Client, Option<Account>> = batch
+            .iter()
+            .map(|tx| (tx.client(), self.accounts.get(&tx.client()).cloned()))
+            .collect();
+        let history: HashMap<TxId, Option<CompletedTx>> = batch
+            .iter()
+            .map(|tx| (tx.id(), self.tx_history.get(tx.id()).cloned()))
+            .collect();
+        let (totals, highest_tx_id) = (self.totals, self.highest_tx_id);
+        let diagnostics = self.diagnostics.len();
+        let stats = self.stats.clone();
+
+        let Err(error) = batch.iter().try_for_each(|tx| self.process_transaction(tx)) else {
+            return Ok(());
+        };
+        for (client, account) in accounts {
+            match account {
+                Some(account) => self.accounts.insert(client, account),
+                None => self.accounts.remove(&client),
+            };
+        }
+        for (id, tx) in history {
+            match tx {
+                Some(tx) => self.tx_history.restore(id, tx),
+                None => self.tx_history.remove(id),
+            }
+        }
+        self.totals = totals;
+        self.highest_tx_id = highest_tx_id;
+        self.diagnostics.truncate(diagnostics);
+        self.stats = stats;
+        self.recount_held();
+        Err(error)
+    }
+
+    /// Processes every transaction record in `reader`.
+    ///
+    /// Errors from [`PaymentEngine::process_transaction`] are ignored,
+    /// only malformed CSV aborts processing. A malformed last record that isn't terminated by
+    /// a newline is instead reported as truncated input, an [`io::ErrorKind::UnexpectedEof`].
+    pub fn process_csv<R: io::Read>(&mut self, reader: R) -> Result<(), csv::Error> {
+        // ignore errors from process_transaction
+        self.process_records(reader, |_, _, _, _| Ok(()))
+    }
+
+    /// Processes every transaction record in `reader`, like [`PaymentEngine::process_csv`],
+    /// but writes each rejected record to `dead_letter`, with the reason in an extra column,
+    /// so that they may be fixed and replayed.
+    ///
+    /// Nothing at all is written if no records are rejected.
+    pub fn process_csv_with_dead_letter<R: io::Read, W: io::Write>(
+        &mut self,
+        reader: R,
+        dead_letter: W,
+    ) -> Result<(), csv::Error> {
+        let mut csv_out = csv::WriterBuilder::new()
+            .flexible(true)
+            .from_writer(dead_letter);
+        let mut header_written = false;
+        self.process_records(reader, |headers, record, _, result| {
+            let Err(error) = result else {
+                return Ok(());
+            };
+            if !header_written {
+                let mut header = headers.clone();
+                header.push_field("reason");
+                csv_out.write_record(&header)?;
+                header_written = true;
+            }
+            // pad out short records, so the reason always lands in its own column
+            let mut row = record.clone();
+            while row.len() < headers.len() {
+                row.push_field("");
+            }
+            row.push_field(&error.to_string());
+            csv_out.write_record(&row)
+        })?;
+        csv_out.flush()?;
+        Ok(())
+    }
+
+    /// Processes every transaction record in `reader`, like [`PaymentEngine::process_csv`],
+    /// but stops at the first rejected transaction, reporting it as an error.
+    ///
+    /// The transactions before the rejected one remain applied.
+    pub fn process_csv_strict<R: io::Read>(&mut self, reader: R) -> Result<(), StrictError> {
+        self.process_records(reader, |_, record, _, result| {
+            result.map_err(|error| StrictError::Rejected {
+                line: record.position().map_or(0, csv::Position::line),
+                error,
+            })
+        })
+    }
+
+    /// Reverses every transaction record in `reader`, from the last to the first, undoing
+    /// their effects to reconstruct the accounts as they were before them, such as for
+    /// "what-if" analysis from a snapshot taken after they were applied.
+    ///
+    /// Each transaction must have been applied, and be reversible as described by
+    /// [`Account::reverse_transaction`]. Nothing is reversed if any isn't.
+    pub fn reverse_csv<R: io::Read>(&mut self, reader: R) -> Result<(), ReverseError> {
+        let mut records = TransactionReader::new(reader, &self.config, self.custom_types())?;
+        let mut transactions = Vec::new();
+        while records.read_record()? {
+            transactions.push((records.line(), records.transaction()?));
+        }
+        // everything the reversal may touch, as it was before. Accounts are never opened.
+        let accounts: HashMap<Client, Account> = transactions
+            .iter()
+            .filter_map(|(_, tx)| self.accounts.get(&tx.client()))
+            .map(|account| (account.client(), account.clone()))
+            .collect();
+        let history: HashMap<TxId, CompletedTx> = transactions
+            .iter()
+            .filter_map(|(_, tx)| Some((tx.id(), self.tx_history.get(tx.id())?.clone())))
+            .collect();
+
+        for (line, tx) in transactions.into_iter().rev() {
+            let result = match self.accounts.get_mut(&tx.client()) {
+                Some(account) => account.reverse_transaction(&tx, &mut self.tx_history),
+                None => Err(account::Irreversible::NotRecorded(tx.id())),
+            };
+            if let Err(error) = result {
+                self.accounts.extend(accounts);
+                for (id, tx) in history {
+                    self.tx_history.restore(id, tx);
+                }
+                return Err(ReverseError::Irreversible { line, error });
+            }
+        }
+        self.recount_held();
+        Ok(())
+    }
+
+    /// Processes every transaction record in `reader`, like [`PaymentEngine::process_csv`],
+    /// but writes each transaction applied to `normalized`, in the canonical form of a
+    /// transaction record, so that it may be diffed against the input to see what was rejected.
+    pub fn process_csv_normalized<R: io::Read, W: io::Write>(
+        &mut self,
+        reader: R,
+        normalized: W,
+    ) -> Result<(), csv::Error> {
+        let mut csv_out = csv::Writer::from_writer(normalized);
+        self.process_records(reader, |_, _, tx, result| match result {
+            Ok(()) => csv_out.serialize(tx),
+            Err(_) => Ok(()),
+        })?;
+        csv_out.flush()?;
+        Ok(())
+    }
+
+    /// Counts a transaction rejected while reading it, before it reached
+    /// [`PaymentEngine::process_transaction`], just as that counts its own rejections
+    fn reject(&mut self, error: account::Error) -> Result<(), account::Error> {
+        self.stats.read += 1;
+        *self.stats.rejected.entry(error.name()).or_default() += 1;
+        Err(error)
+    }
+
+    /// Processes every record in `reader`, calling `on_processed` with the headers,
+    /// each record and its transaction, and the result of [`PaymentEngine::process_transaction`],
+    /// or of its rejection as read, for withdrawals rounding to zero.
+    fn process_records<R: io::Read, E: From<csv::Error>>(
+        &mut self,
+        reader: R,
+        mut on_processed: impl FnMut(
+            &csv::StringRecord,
+            &csv::StringRecord,
+            &Transaction,
+            Result<(), account::Error>,
+        ) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut records = TransactionReader::new(reader, &self.config, self.custom_types())?;
+        while records.read_record()? {
+            if self.stopped() {
+                self.diagnostics.push(Diagnostic::Interrupted {
+                    line: records.line(),
+                });
+                break;
+            }
+            self.within_max_memory(records.line())?;
+            let tx = records.transaction()?;
+            let result = match records.rounded_to_zero.take() {
+                Some(written) => {
+                    self.diagnostics.push(Diagnostic::RoundedToZero {
+                        line: records.line(),
+                        tx: tx.id(),
+                        written,
+                        scale: records.scale,
+                    });
+                    self.reject(account::Error::WithdrawalRoundsToZero(tx.id()))
+                }
+                None => self.process_transaction(&tx),
+            };
+            on_processed(&records.headers, &records.record, &tx, result)?;
+        }
+        Ok(())
+    }
+
+    /// Reads every transaction record in `reader`, without applying any, for the disputes,
+    /// resolves, and chargebacks referencing an id that's neither already recorded nor that
+    /// of any deposit or withdrawal in `reader`, as [`Diagnostic::DanglingReference`]s.
+    ///
+    /// Catches structural problems with an input before any of it is processed.
+    pub fn dangling_references<R: io::Read>(
+        &self,
+        reader: R,
+    ) -> Result<Vec<Diagnostic>, csv::Error> {
+        let mut records = TransactionReader::new(reader, &self.config, self.custom_types())?;
+        let mut seen = HashSet::new();
+        let mut references = Vec::new();
+        while records.read_record()? {
+            let tx = records.transaction()?;
+            match tx.action() {
+                Action::Deposit { .. }
+                | Action::Withdrawal { .. }
+                | Action::WithdrawAll
+                | Action::Adjust { .. }
+                | Action::Custom { .. } => {
+                    seen.insert(tx.id());
+                }
+                Action::Dispute
+                | Action::PartialDispute { .. }
+                | Action::Resolve
+                | Action::Chargeback => references.push((records.line(), tx.id())),
+                Action::Close => (),
+            }
+        }
+        Ok(references
+            .into_iter()
+            .filter(|&(_, tx)| !seen.contains(&tx) && !self.tx_history.contains(tx))
+            .map(|(line, tx)| Diagnostic::DanglingReference { line, tx })
+            .collect())
+    }
+
+    /// Reads every transaction record in `reader`, without applying any, for suspicious
+    /// sequences of disputes, resolves, and chargebacks that are likely a malformed export:
+    /// [`Diagnostic::RepeatedReference`]s, such as a resolve straight after another resolve
+    /// of the same transaction, and [`Diagnostic::ReferenceBeforeTransaction`]s.
+    pub fn suspicious_sequences<R: io::Read>(
+        &self,
+        reader: R,
+    ) -> Result<Vec<Diagnostic>, csv::Error> {
+        let mut records = TransactionReader::new(reader, &self.config, self.custom_types())?;
+        let mut seen = HashSet::new();
+        // the type of the last dispute, resolve, or chargeback of each id
+        let mut last_reference = HashMap::new();
+        let mut suspicious = Vec::new();
+        while records.read_record()? {
+            let tx = records.transaction()?;
+            let (line, id) = (records.line(), tx.id());
+            match tx.action() {
+                Action::Deposit { .. }
+                | Action::Withdrawal { .. }
+                | Action::WithdrawAll
+                | Action::Adjust { .. }
+                | Action::Custom { .. } => {
+                    seen.insert(id);
+                }
+                Action::Dispute
+                | Action::PartialDispute { .. }
+                | Action::Resolve
+                | Action::Chargeback => {
+                    let action = tx.action().type_name();
+                    if !seen.contains(&id) && !self.tx_history.contains(id) {
+                        suspicious.push(Diagnostic::ReferenceBeforeTransaction { line, tx: id });
+                    }
+                    if last_reference.insert(id, action) == Some(action) {
+                        suspicious.push(Diagnostic::RepeatedReference {
+                            line,
+                            tx: id,
+                            action,
+                        });
+                    }
+                }
+                Action::Close => (),
+            }
+        }
+        Ok(suspicious)
+    }
+
+    /// Estimates the memory used by the accounts and transaction history, in bytes,
+    /// from their number of entries. Doesn't count the spare capacity of their tables.
+    pub fn estimated_memory(&self) -> usize {
+        self.accounts.len() * ACCOUNT_ENTRY_SIZE + self.tx_history.len() * HISTORY_ENTRY_SIZE
+    }
+
+    /// Fails reading the record on `line` if applying it could exceed the configured maximum
+    /// memory, opening an account and recording a transaction both.
+    fn within_max_memory(&self, line: u64) -> Result<(), csv::Error> {
+        let Some(max) = self.config.max_memory else {
+            return Ok(());
+        };
+        let estimate = self.estimated_memory();
+        if estimate + ACCOUNT_ENTRY_SIZE + HISTORY_ENTRY_SIZE <= max {
+            return Ok(());
+        }
+        Err(io::Error::new(
+            io::ErrorKind::OutOfMemory,
+            format!(
+                "line {}: estimated memory use of {} bytes, for {} accounts and {} transactions, \
+                 would exceed the maximum of {} bytes",
+                line,
+                estimate,
+                self.accounts.len(),
+                self.tx_history.len(),
+                max
+            ),
+        )
+        .into())
+    }
+
+    /// Sums the held funds afresh, after replacing accounts wholesale
+    fn recount_held(&mut self) {
+        self.total_held = self.accounts.values().map(Account::held).sum();
+    }
+
+    fn stopped(&self) -> bool {
+        self.config
+            .stop
+            .as_ref()
+            .is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    /// Processes every transaction record in an in-memory CSV, such as an upload.
+    ///
+    /// See [`PaymentEngine::process_csv`].
+    pub fn process_csv_bytes(&mut self, bytes: &[u8]) -> Result<(), csv::Error> {
+        self.process_csv(bytes)
+    }
+
+    /// Applies newline-delimited JSON transactions from `reader`, each an object with the
+    /// same fields as a transaction record, as in
+    /// `{"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"}`.
+    ///
+    /// Each line is answered by a line of JSON on `writer`, either the client's account as in
+    /// the JSON report, or `{"error": ...}` if the transaction was malformed or rejected.
+    pub fn process_ndjson<R: io::BufRead, W: io::Write>(
+        &mut self,
+        reader: R,
+        mut writer: W,
+    ) -> io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let result = serde_json::from_str::<Transaction>(&line)
+                .map_err(|e| e.to_string())
+                .and_then(|tx| {
+                    self.process_transaction(&tx)
+                        .map(|()| tx.client())
+                        .map_err(|e| e.to_string())
+                });
+            match result {
+                Ok(client) => {
+                    // skipped transactions may not have opened the account
+                    let unopened = Account::new(client);
+                    let row = report::Row {
+                        account: self.accounts.get(&client).unwrap_or(&unopened),
+                        options: &ReportOptions::default(),
+                        derived_total: None,
+                    };
+                    serde_json::to_writer(&mut writer, &row)?;
+                }
+                Err(error) => {
+                    serde_json::to_writer(&mut writer, &serde_json::json!({ "error": error }))?
+                }
+            }
+            writeln!(writer)?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Serves [`PaymentEngine::process_ndjson`] over a Unix domain socket,
+    /// one connection at a time, all applied to this engine.
+    ///
+    /// An error reading from or writing to a connection, e.g. the client hanging up early,
+    /// only closes that connection.
+    #[cfg(all(unix, feature = "uds"))]
+    pub fn serve_uds(&mut self, listener: &std::os::unix::net::UnixListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let _ = self.process_ndjson(io::BufReader::new(&stream), &stream);
+        }
+        Ok(())
+    }
+
+    /// Loads the accounts from a report previously written by
+    /// [`PaymentEngine::write_report`], replacing any accounts of the same clients.
+    ///
+    /// Reports don't carry the transaction history, so disputes can't
+    /// reference transactions processed before the report was written.
+    pub fn load_report<R: io::Read>(&mut self, reader: R) -> Result<(), csv::Error> {
+        let mut csv_in = csv_reader_builder().from_reader(reader);
+        for account in csv_in.deserialize() {
+            let account: Account = account?;
+            self.accounts.insert(account.client(), account);
+        }
+        self.recount_held();
+        Ok(())
+    }
+
+    /// Every transaction currently in dispute, sorted by id, for manual action
+    pub fn open_disputes(&self) -> Vec<OpenDispute> {
+        self.tx_history
+            .disputed()
+            .into_iter()
+            .map(|(tx, client, amount)| OpenDispute { tx, client, amount })
+            .collect()
+    }
+
+    /// Resolves every open dispute, returning the held funds to available,
+    /// just as if a resolve record had been processed for each.
+    ///
+    /// Returns the disputes that were resolved, none if [`Config::only_types`] skips resolves.
+    pub fn resolve_all(&mut self) -> Vec<OpenDispute> {
+        self.open_disputes()
+            .into_iter()
+            .filter(|dispute| {
+                let resolve = Transaction::new(Action::new_resolve(), dispute.client, dispute.tx);
+                // skipped resolves succeed too, without resolving anything
+                self.process_transaction(&resolve).is_ok()
+                    && self
+                        .tx_history
+                        .get(dispute.tx)
+                        .is_some_and(|completed| !completed.disputed)
+            })
+            .collect()
+    }
+
+    /// The account of `client`, if it has one
+    pub fn account(&self, client: Client) -> Option<&Account> {
+        self.accounts.get(&client)
+    }
+
+    /// Every account, in no particular order
+    pub fn accounts(&self) -> impl Iterator<Item = &Account> {
+        self.accounts.values()
+    }
+
+    /// Consumes the engine, returning every account's report, sorted by client,
+    /// for callers handing off the final state with no further need of the engine.
+    pub fn into_reports(self) -> Vec<AccountReport> {
+        let mut reports: Vec<AccountReport> = self
+            .accounts
+            .into_values()
+            .map(|account| AccountReport::from(&account))
+            .collect();
+        reports.sort_unstable_by_key(|report| report.client);
+        reports
+    }
+
+    /// Captures the accounts and transaction history, sorted by client and id respectively
+    pub fn snapshot(&self) -> Snapshot {
+        let accounts = self
+            .sorted_accounts()
+            .into_iter()
+            .map(snapshot::AccountRecord::of)
+            .collect();
+        let mut history: Vec<_> = self
+            .tx_history
+            .entries()
+            .map(|(tx, completed)| snapshot::HistoryRecord {
+                tx,
+                completed: completed.clone(),
+            })
+            .collect();
+        history.sort_unstable_by_key(|record| record.tx);
+        Snapshot { accounts, history }
+    }
+
+    /// The clients whose accounts differ from the `since` snapshot, or are missing from it,
+    /// such as to report only the changes since the snapshot was saved.
+    pub fn changed_since(&self, since: &Snapshot) -> HashSet<Client> {
+        let before: HashMap<Client, &snapshot::AccountRecord> = since
+            .accounts
+            .iter()
+            .map(|account| (account.client, account))
+            .collect();
+        self.accounts
+            .values()
+            .filter(|account| {
+                before.get(&account.client()) != Some(&&snapshot::AccountRecord::of(account))
+            })
+            .map(Account::client)
+            .collect()
+    }
+
+    /// Restores the accounts and transaction history of a verified `snapshot`,
+    /// replacing any accounts and transactions of the same clients and ids.
+    pub fn restore_snapshot(&mut self, snapshot: Snapshot) -> Result<(), Violation> {
+        snapshot.verify()?;
+        for account in snapshot.accounts {
+            self.accounts.insert(
+                account.client,
+                Account::restore(
+                    account.client,
+                    account.available,
+                    account.held,
+                    account.locked,
+                    account.closed,
+                ),
+            );
+        }
+        for record in snapshot.history {
+            self.tx_history.restore(record.tx, record.completed);
+        }
+        self.recount_held();
+        Ok(())
+    }
+
+    /// Combines a `shard`, which processed a disjoint set of clients, into this engine,
+    /// such as after processing each shard in parallel.
+    ///
+    /// Nothing is merged if any client or transaction appears in both engines.
+    pub fn merge(&mut self, shard: PaymentEngine) -> Result<(), MergeError> {
+        if let Some(&client) = shard
+            .accounts
+            .keys()
+            .find(|client| self.accounts.contains_key(client))
+        {
+            return Err(MergeError::SharedClient(client));
+        }
+        if let Some((tx, _)) = shard
+            .tx_history
+            .entries()
+            .find(|&(tx, _)| self.tx_history.contains(tx))
+        {
+            return Err(MergeError::SharedTx(tx));
+        }
+        self.accounts.extend(shard.accounts);
+        for (tx, completed) in shard.tx_history.into_entries() {
+            self.tx_history.restore(tx, completed);
+        }
+        self.highest_tx_id = self.highest_tx_id.max(shard.highest_tx_id);
+        self.diagnostics.extend(shard.diagnostics);
+        self.keyed_results.extend(shard.keyed_results);
+        self.previously_applied.extend(shard.previously_applied);
+        self.totals.deposits += shard.totals.deposits;
+        self.totals.withdrawals += shard.totals.withdrawals;
+        self.totals.charged_back += shard.totals.charged_back;
+        self.totals.adjustments += shard.totals.adjustments;
+        self.total_held += shard.total_held;
+        self.stats.read += shard.stats.read;
+        self.stats.applied += shard.stats.applied;
+        self.stats.skipped += shard.stats.skipped;
+        for (name, count) in shard.stats.rejected {
+            *self.stats.rejected.entry(name).or_default() += count;
+        }
+        Ok(())
+    }
+
+    /// Writes a snapshot of the engine as JSON, see [`PaymentEngine::snapshot`]
+    pub fn save_snapshot<W: io::Write>(&self, writer: W) -> Result<(), serde_json::Error> {
+        serde_json::to_writer(writer, &self.snapshot())
+    }
+
+    /// Reads and restores a snapshot saved by [`PaymentEngine::save_snapshot`]
+    pub fn load_snapshot<R: io::Read>(&mut self, reader: R) -> Result<(), SnapshotError> {
+        let snapshot: Snapshot = serde_json::from_reader(reader)?;
+        self.restore_snapshot(snapshot)?;
+        Ok(())
+    }
+
+    /// An engine configured by `config`, resumed from the snapshot file at `path`,
+    /// as saved by [`PaymentEngine::save_snapshot`].
+    pub fn from_snapshot_file(
+        path: impl AsRef<Path>,
+        config: Config,
+    ) -> Result<Self, SnapshotError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| SnapshotError::Io(path.to_owned(), e))?;
+        let mut engine = Self::with_config(config);
+        engine.load_snapshot(io::BufReader::new(file))?;
+        Ok(engine)
+    }
+
+    /// Loads the ids of deposits and withdrawals applied in prior runs, one per line,
+    /// as written by [`PaymentEngine::write_applied_ids`].
+    ///
+    /// This lets overlapping inputs be processed day after day, without a full snapshot.
+    pub fn load_applied_ids<R: io::BufRead>(&mut self, reader: R) -> io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let id = line.parse().map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid transaction id {:?}: {}", line, e),
+                )
+            })?;
+            self.previously_applied.insert(id);
+        }
+        Ok(())
+    }
+
+    /// Writes the ids of every deposit and withdrawal applied, in this run or a prior one,
+    /// sorted, one per line.
+    pub fn write_applied_ids<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut ids: Vec<TxId> = self
+            .previously_applied
+            .iter()
+            .copied()
+            .chain(self.tx_history.entries().map(|(id, _)| id))
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        for id in ids {
+            writeln!(writer, "{}", id)?;
+        }
+        writer.flush()
+    }
+
+    /// Diagnostics emitted while processing transactions
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Checks that no client's total funds exceed the sum of their
+    /// recorded deposits less their recorded withdrawals.
+    ///
+    /// Accounts loaded from a report have no recorded transactions,
+    /// and so will likely be flagged.
+    pub fn audit(&self) -> Vec<Diagnostic> {
+        let recorded = self.tx_history.net_recorded_by_client();
+        self.sorted_accounts()
+            .into_iter()
+            .filter_map(|account| {
+                let client = account.client();
+                let total = account.total();
+                let recorded = recorded.get(&client).copied().unwrap_or(Money::ZERO);
+                (total > recorded).then_some(Diagnostic::FundsExceedRecorded {
+                    client,
+                    total,
+                    recorded,
+                })
+            })
+            .collect()
+    }
+
+    /// Counts of the transactions processed so far, by how they were processed.
+    ///
+    /// A batch rolled back by [`PaymentEngine::process_batch`] isn't counted at all.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Sums the transactions applied and the accounts' balances, see [`Reconciliation`]
+    pub fn reconciliation(&self) -> Reconciliation {
+        Reconciliation {
+            held: self.accounts.values().map(Account::held).sum(),
+            available: self.accounts.values().map(Account::available).sum(),
+            ..self.totals
+        }
+    }
+
+    /// Every deposit, withdrawal, and adjustment recorded, along with the state of any dispute
+    pub fn tx_history(&self) -> &TxHistory {
+        &self.tx_history
+    }
+
+    /// Writes every recorded transaction as CSV, sorted by id, with the columns
+    /// `tx`, `kind`, `amount`, and `disputed`, for debugging the state of disputes.
+    pub fn write_history<W: io::Write>(&self, writer: W) -> Result<(), csv::Error> {
+        #[derive(serde::Serialize)]
+        struct HistoryRow {
+            tx: TxId,
+            kind: CompletedTxKind,
+            amount: Money,
+            disputed: bool,
+        }
+        let mut entries: Vec<_> = self.tx_history.entries().collect();
+        entries.sort_unstable_by_key(|&(tx, _)| tx);
+        let mut csv_out = csv::Writer::from_writer(writer);
+        for (tx, completed) in entries {
+            csv_out.serialize(HistoryRow {
+                tx,
+                kind: completed.kind,
+                amount: completed.amount,
+                disputed: completed.disputed,
+            })?;
+        }
+        csv_out.flush()?;
+        Ok(())
+    }
+
+    /// Writes the [`PaymentEngine::reconciliation`] as a single row of CSV
+    pub fn write_reconciliation<W: io::Write>(&self, writer: W) -> Result<(), csv::Error> {
+        let mut csv_out = csv::Writer::from_writer(writer);
+        csv_out.serialize(self.reconciliation())?;
+        csv_out.flush()?;
+        Ok(())
+    }
+
+    /// Writes a report of every account, sorted by client.
+    pub fn write_report<W: io::Write>(
+        &self,
+        mut writer: W,
+        options: &ReportOptions,
+    ) -> Result<(), csv::Error> {
+        // converted before any is written, so that a failure leaves no partial report
+        let rows = self
+            .report_rows(options)
+            .map_err(report::conversion_failed)?;
+        if options.bom {
+            writer.write_all(report::BOM)?;
+        }
+        let mut csv_out = csv::WriterBuilder::new()
+            .has_headers(true)
+            .from_writer(writer);
+        for (i, row) in rows.into_iter().enumerate() {
+            csv_out.serialize(row)?;
+            if options
+                .flush_every
+                .is_some_and(|every| (i + 1) % every == 0)
+            {
+                csv_out.flush()?;
+            }
+        }
+        csv_out.flush()?;
+        Ok(())
+    }
+
+    /// Writes a report of every account as a JSON array, sorted by client.
+    pub fn write_json_report<W: io::Write>(
+        &self,
+        writer: W,
+        options: &ReportOptions,
+    ) -> Result<(), serde_json::Error> {
+        let rows = self
+            .report_rows(options)
+            .map_err(|e| serde_json::Error::io(report::conversion_failed(e)))?;
+        if options.pretty {
+            serde_json::to_writer_pretty(writer, &rows)
+        } else {
+            serde_json::to_writer(writer, &rows)
+        }
+    }
+
+    /// Writes a report of every account as JSON Lines, one object per line, sorted by client.
+    ///
+    /// Each line is flushed as it's written, for streaming consumers.
+    pub fn write_json_lines_report<W: io::Write>(
+        &self,
+        mut writer: W,
+        options: &ReportOptions,
+    ) -> Result<(), serde_json::Error> {
+        let rows = self
+            .report_rows(options)
+            .map_err(|e| serde_json::Error::io(report::conversion_failed(e)))?;
+        for row in rows {
+            serde_json::to_writer(&mut writer, &row)?;
+            writeln!(writer).map_err(serde_json::Error::io)?;
+            writer.flush().map_err(serde_json::Error::io)?;
+        }
+        Ok(())
+    }
+
+    /// Reports every account to `sink`, sorted by client, as
+    /// `(client, available, held, total, locked)`, for outputs other than CSV and JSON.
+    ///
+    /// Only `limit` and `locked_only` of the `options` apply.
+    pub fn report_with<F>(&self, options: &ReportOptions, mut sink: F)
+    where
+        F: FnMut(Client, Money, Money, Money, bool),
+    {
+        for account in self.reported_accounts(options) {
+            sink(
+                account.client(),
+                account.available(),
+                account.held(),
+                account.total(),
+                account.is_locked(),
+            );
+        }
+    }
+
+    fn sorted_accounts(&self) -> Vec<&Account> {
+        let mut accounts: Vec<&Account> = self.accounts.values().collect();
+        accounts.sort_unstable_by_key(|account| account.client());
+        accounts
+    }
+
+    fn reported_accounts(&self, options: &ReportOptions) -> Vec<&Account> {
+        self.sorted_accounts()
+            .into_iter()
+            .filter(|account| account.is_locked() || !options.locked_only)
+            .filter(|account| {
+                options
+                    .clients
+                    .as_ref()
+                    .is_none_or(|clients| clients.contains(&account.client()))
+            })
+            .take(options.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+
+    fn report_rows<'a>(
+        &'a self,
+        options: &'a ReportOptions,
+    ) -> Result<Vec<report::Row<'a>>, currency::ConversionError> {
+        self.reported_accounts(options)
+            .into_iter()
+            .map(|account| {
+                Ok(report::Row {
+                    account,
+                    options,
+                    derived_total: options.derived_total(account.total())?,
+                })
+            })
+            .collect()
+    }
+}
+
+impl<'a> IntoIterator for &'a PaymentEngine {
+    type Item = &'a Account;
+    type IntoIter = std::collections::hash_map::Values<'a, Client, Account>;
+
+    /// Iterates over every account, in no particular order
+    fn into_iter(self) -> Self::IntoIter {
+        self.accounts.values()
+    }
+}
@@ -0,0 +1,25 @@
+//! Engine-wide sums, for reconciling a run against the source system's own totals
+//!
+use crate::account::Money;
+use serde::Serialize;
+
+/// The sums of every transaction applied, and of every account's current balances.
+///
+/// For a run starting from nothing,
+/// `deposits - withdrawals - charged_back + adjustments == held + available`.
+/// Merged reports and restored snapshots bring balances without the transactions behind them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Reconciliation {
+    /// the sum of every deposit applied
+    pub deposits: Money,
+    /// the sum of every withdrawal applied
+    pub withdrawals: Money,
+    /// the sum of every disputed amount charged back
+    pub charged_back: Money,
+    /// the sum of every adjustment applied, negative if they debited more than they credited
+    pub adjustments: Money,
+    /// the sum of every account's held funds
+    pub held: Money,
+    /// the sum of every account's available funds, which may be negative
+    pub available: Money,
+}
@@ -0,0 +1,177 @@
+//! Options for the [`PaymentEngine`](super::PaymentEngine)'s reports
+//!
+use crate::account::{
+    currency::{self, ConversionError, Rates},
+    Account, Client, Money,
+};
+use serde::{ser::SerializeStruct, Serialize};
+use std::collections::HashSet;
+use std::io;
+
+/// The default options report every column of every account as CSV
+#[derive(Clone, Debug, Default)]
+pub struct ReportOptions {
+    /// indent JSON reports, one field per line
+    pub pretty: bool,
+    /// leave out the `total` column, for consumers that compute it themselves
+    pub omit_total: bool,
+    /// report only the first `limit` accounts, for previewing large runs
+    pub limit: Option<usize>,
+    /// report only locked accounts, for compliance review of frozen funds
+    pub locked_only: bool,
+    /// report only these clients, such as those [changed since](super::PaymentEngine::changed_since)
+    /// a snapshot
+    pub clients: Option<HashSet<Client>>,
+    /// write amounts with as few decimal places as they need, as `1.5` rather than `1.5000`
+    pub compact: bool,
+    /// write amounts with exactly this currency's [decimal places](currency::scale_of),
+    /// such as none for `JPY`, whether or not they're `compact`
+    pub currency: Option<String>,
+    /// add a `derived_total` column after the others, of each account's total converted from
+    /// the `currency` into these rates' reporting currency. Such totals are derived, and only
+    /// as exact as the rates, while the balances are still reported exactly. Without a
+    /// `currency` there's nothing to convert from, so no column is added.
+    pub rates: Option<Rates>,
+    /// flush CSV reports after every `flush_every` rows, so consumers see progress
+    pub flush_every: Option<usize>,
+    /// start CSV reports with a UTF-8 byte order mark, as some Windows tools expect
+    pub bom: bool,
+    /// add a `closed` column after `locked`, for when accounts may be
+    /// [closed](crate::account::Action::Close)
+    pub closed: bool,
+}
+
+impl ReportOptions {
+    /// The `total` converted by the `rates`, if they and the `currency` are set
+    pub(super) fn derived_total(&self, total: Money) -> Result<Option<Money>, ConversionError> {
+        match (&self.rates, &self.currency) {
+            (Some(rates), Some(currency)) => {
+                rates.equivalent([(currency.as_str(), total)]).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// A failed conversion of the derived totals, as the I/O error of the report's writer
+pub(super) fn conversion_failed(error: ConversionError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// An account's final balances and standing, as in a row of the report
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct AccountReport {
+    pub client: Client,
+    pub available: Money,
+    pub held: Money,
+    pub total: Money,
+    pub locked: bool,
+    pub closed: bool,
+}
+
+impl From<&Account> for AccountReport {
+    fn from(account: &Account) -> Self {
+        Self {
+            client: account.client(),
+            available: account.available(),
+            held: account.held(),
+            total: account.total(),
+            locked: account.is_locked(),
+            closed: account.is_closed(),
+        }
+    }
+}
+
+impl AccountReport {
+    /// The report's columns, in order, with their types as JSON, for downstream systems
+    /// generating loaders: `client` is an unsigned integer no larger than [`Client::MAX`],
+    /// the amounts are decimals of up to [`Money::SCALE`] places, and `locked` is a boolean.
+    /// The `closed` column of [`ReportOptions::closed`] is left out, like other optional ones.
+    pub fn schema() -> serde_json::Value {
+        let money = |name: &str| serde_json::json!({ "name": name, "type": "decimal", "scale": Money::SCALE });
+        serde_json::json!({
+            "columns": [
+                { "name": "client", "type": "integer", "minimum": 0, "maximum": Client::MAX },
+                money("available"),
+                money("held"),
+                money("total"),
+                { "name": "locked", "type": "boolean" },
+            ]
+        })
+    }
+}
+
+/// The UTF-8 encoding of U+FEFF, the byte order mark
+pub(super) const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// An account's row in the report, serialized according to the options
+pub(super) struct Row<'a> {
+    pub account: &'a Account,
+    pub options: &'a ReportOptions,
+    /// the account's total in the reporting currency, if converting
+    pub derived_total: Option<Money>,
+}
+
+impl Serialize for Row<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let Row {
+            account,
+            options,
+            derived_total,
+        } = self;
+        let scale = options.currency.as_deref().map(currency::scale_of);
+        let amount = |money| Amount {
+            money,
+            compact: options.compact,
+            scale,
+        };
+        let len = 4
+            + usize::from(!options.omit_total)
+            + usize::from(options.closed)
+            + usize::from(derived_total.is_some());
+        let mut state = serializer.serialize_struct("Account", len)?;
+        state.serialize_field("client", &account.client())?;
+        state.serialize_field("available", &amount(account.available()))?;
+        state.serialize_field("held", &amount(account.held()))?;
+        if !options.omit_total {
+            state.serialize_field("total", &amount(account.total()))?;
+        }
+        state.serialize_field("locked", &account.is_locked())?;
+        if options.closed {
+            state.serialize_field("closed", &account.is_closed())?;
+        }
+        if let Some(derived_total) = *derived_total {
+            // in the reporting currency, so not at the `currency`'s scale
+            let derived_total = Amount {
+                money: derived_total,
+                compact: options.compact,
+                scale: None,
+            };
+            state.serialize_field("derived_total", &derived_total)?;
+        }
+        state.end()
+    }
+}
+
+/// An amount in a [`Row`], which may be written compactly, or at a currency's scale
+struct Amount {
+    money: Money,
+    compact: bool,
+    scale: Option<usize>,
+}
+
+impl Serialize for Amount {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self.scale {
+            Some(scale) => serializer.collect_str(&format_args!("{:.*}", scale, self.money)),
+            None if self.compact => serializer.serialize_str(&self.money.to_compact_string()),
+            None => self.money.serialize(serializer),
+        }
+    }
+}
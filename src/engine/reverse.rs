@@ -0,0 +1,49 @@
+//! Errors reversing transactions, where any irreversible transaction is fatal
+//!
+use crate::account::{Irreversible, TxId};
+use std::fmt::Display;
+
+/// Why [`PaymentEngine::reverse_csv`](super::PaymentEngine::reverse_csv) reversed nothing
+#[derive(Debug)]
+pub enum ReverseError {
+    /// the input couldn't be read or parsed
+    Csv(csv::Error),
+    /// the transaction record on `line` couldn't be reversed
+    Irreversible { line: u64, error: Irreversible },
+}
+
+impl ReverseError {
+    /// The id of the irreversible transaction, if there was one
+    pub fn tx(&self) -> Option<TxId> {
+        match self {
+            ReverseError::Csv(_) => None,
+            ReverseError::Irreversible { error, .. } => Some(error.tx()),
+        }
+    }
+}
+
+impl From<csv::Error> for ReverseError {
+    fn from(error: csv::Error) -> Self {
+        ReverseError::Csv(error)
+    }
+}
+
+impl Display for ReverseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReverseError::Csv(error) => error.fmt(f),
+            ReverseError::Irreversible { line, error } => {
+                write!(f, "line {} can't be reversed: {}", line, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReverseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReverseError::Csv(error) => Some(error),
+            ReverseError::Irreversible { error, .. } => Some(error),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! A `Snapshot` is the complete state of a [`PaymentEngine`](super::PaymentEngine),
+//! both its accounts and transaction history, saved as JSON so processing may resume later.
+//!
+use crate::account::{Account, Client, CompletedTx, Money, TxId};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub(super) accounts: Vec<AccountRecord>,
+    pub(super) history: Vec<HistoryRecord>,
+}
+
+/// An account as saved in a snapshot, matching the report's columns
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct AccountRecord {
+    pub client: Client,
+    pub available: Money,
+    pub held: Money,
+    pub total: Money,
+    pub locked: bool,
+    /// left out unless the account was closed, as snapshots from before closing were
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub closed: bool,
+}
+
+impl AccountRecord {
+    pub fn of(account: &Account) -> Self {
+        Self {
+            client: account.client(),
+            available: account.available(),
+            held: account.held(),
+            total: account.total(),
+            locked: account.is_locked(),
+            closed: account.is_closed(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct HistoryRecord {
+    pub tx: TxId,
+    #[serde(flatten)]
+    pub completed: CompletedTx,
+}
+
+impl Snapshot {
+    /// The lowest client whose account differs from, or is missing from, the `expected` snapshot.
+    pub fn first_mismatched_client(&self, expected: &Snapshot) -> Option<Client> {
+        let by_client = |snapshot: &Snapshot| -> BTreeMap<Client, AccountRecord> {
+            snapshot
+                .accounts
+                .iter()
+                .map(|account| (account.client, account.clone()))
+                .collect()
+        };
+        let (actual, expected) = (by_client(self), by_client(expected));
+        actual
+            .keys()
+            .chain(expected.keys())
+            .filter(|client| actual.get(client) != expected.get(client))
+            .min()
+            .copied()
+    }
+
+    /// Checks the snapshot's internal invariants, returning the first violation found.
+    pub fn verify(&self) -> Result<(), Violation> {
+        for account in &self.accounts {
+            if account.available.checked_add(account.held) != Some(account.total) {
+                return Err(Violation::TotalMismatch(account.client));
+            }
+            if account.held.is_negative() {
+                return Err(Violation::NegativeHeld(account.client));
+            }
+        }
+        for HistoryRecord { tx, completed } in &self.history {
+            if completed.charged_back && completed.disputed {
+                return Err(Violation::DisputedAfterChargeback(*tx));
+            }
+            if completed.charged_back && completed.amount != Money::ZERO {
+                return Err(Violation::ChargebackNotZeroed(*tx));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A broken invariant of a [`Snapshot`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// an account's total isn't the sum of its available and held funds
+    TotalMismatch(Client),
+    /// an account holds negative funds
+    NegativeHeld(Client),
+    /// a transaction is in dispute, despite having been charged back
+    DisputedAfterChargeback(TxId),
+    /// a transaction has been charged back, but some of its amount remains
+    ChargebackNotZeroed(TxId),
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::TotalMismatch(client) => write!(
+                f,
+                "client {}'s total is not the sum of available and held funds",
+                client
+            ),
+            Violation::NegativeHeld(client) => write!(f, "client {} holds negative funds", client),
+            Violation::DisputedAfterChargeback(tx) => write!(
+                f,
+                "transaction {} is disputed, but was already charged back",
+                tx
+            ),
+            Violation::ChargebackNotZeroed(tx) => write!(
+                f,
+                "transaction {} was charged back, but its amount wasn't zeroed",
+                tx
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Violation {}
+
+/// An error loading a snapshot
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// the snapshot file at the path couldn't be opened or read
+    Io(PathBuf, io::Error),
+    Json(serde_json::Error),
+    Violation(Violation),
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::Io(path, e) => {
+                write!(f, "can't read snapshot {}: {}", path.display(), e)
+            }
+            SnapshotError::Json(e) => write!(f, "malformed snapshot: {}", e),
+            SnapshotError::Violation(violation) => write!(f, "invalid snapshot: {}", violation),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(e: serde_json::Error) -> Self {
+        SnapshotError::Json(e)
+    }
+}
+
+impl From<Violation> for SnapshotError {
+    fn from(violation: Violation) -> Self {
+        SnapshotError::Violation(violation)
+    }
+}
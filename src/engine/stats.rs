@@ -0,0 +1,38 @@
+//! `Stats` counts the transactions a [`PaymentEngine`](super::PaymentEngine) has processed
+//!
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// every transaction submitted, whether applied, skipped, or rejected
+    pub read: usize,
+    pub applied: usize,
+    /// deposits and withdrawals skipped as applied in a prior run, or as duplicates already
+    /// applied under [`Policy::idempotent_duplicates`](crate::account::Policy::idempotent_duplicates),
+    /// and transactions of the types skipped by [`Config::only_types`](super::Config::only_types)
+    pub skipped: usize,
+    /// the rejected transactions, counted by the [`name`](crate::account::Error::name) of why
+    pub rejected: BTreeMap<&'static str, usize>,
+}
+
+impl Stats {
+    /// The number of rejected transactions, of any reason
+    pub fn rejected_total(&self) -> usize {
+        self.rejected.values().sum()
+    }
+}
+
+// a line per count, with each reason for rejection indented under the total
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "read: {}", self.read)?;
+        writeln!(f, "applied: {}", self.applied)?;
+        writeln!(f, "skipped: {}", self.skipped)?;
+        writeln!(f, "rejected: {}", self.rejected_total())?;
+        for (name, count) in &self.rejected {
+            writeln!(f, "  {}: {}", name, count)?;
+        }
+        Ok(())
+    }
+}
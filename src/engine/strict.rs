@@ -0,0 +1,49 @@
+//! Errors processing transactions strictly, where any rejected transaction is fatal
+//!
+use crate::account::{self, TxId};
+use std::fmt::Display;
+
+/// Why [`PaymentEngine::process_csv_strict`](super::PaymentEngine::process_csv_strict) stopped
+#[derive(Debug)]
+pub enum StrictError {
+    /// the input couldn't be read or parsed
+    Csv(csv::Error),
+    /// the transaction record on `line` was rejected
+    Rejected { line: u64, error: account::Error },
+}
+
+impl StrictError {
+    /// The id of the rejected transaction, if one was rejected
+    pub fn tx(&self) -> Option<TxId> {
+        match self {
+            StrictError::Csv(_) => None,
+            StrictError::Rejected { error, .. } => Some(error.tx()),
+        }
+    }
+}
+
+impl From<csv::Error> for StrictError {
+    fn from(error: csv::Error) -> Self {
+        StrictError::Csv(error)
+    }
+}
+
+impl Display for StrictError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StrictError::Csv(error) => error.fmt(f),
+            StrictError::Rejected { line, error } => {
+                write!(f, "line {} was rejected: {}", line, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StrictError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StrictError::Csv(error) => Some(error),
+            StrictError::Rejected { error, .. } => Some(error),
+        }
+    }
+}
@@ -0,0 +1,2320 @@
+// I (ab)use the underscore as a placeholder for the decimal point in this file
+#![allow(clippy::inconsistent_digit_grouping)]
+use super::*;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Writes `engine`'s report to a string
+fn report_of(engine: &PaymentEngine) -> String {
+    let mut out = Vec::new();
+    engine
+        .write_report(&mut out, &ReportOptions::default())
+        .unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+/// Each account's `(client, available, held)`, sorted by client
+fn balances_of(engine: &PaymentEngine) -> Vec<(Client, Money, Money)> {
+    let mut balances: Vec<_> = engine
+        .accounts()
+        .map(|account| (account.client(), account.available(), account.held()))
+        .collect();
+    balances.sort_unstable();
+    balances
+}
+
+#[test]
+fn merge_into_existing_report() {
+    // the first run writes an initial report
+    let mut first = PaymentEngine::new();
+    first
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             deposit,2,2,20\n\
+             deposit,3,3,30\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let initial = report_of(&first);
+    assert_eq!(
+        initial,
+        "client,available,held,total,locked\n\
+         1,10.0000,0.0000,10.0000,false\n\
+         2,20.0000,0.0000,20.0000,false\n\
+         3,30.0000,0.0000,30.0000,false\n"
+    );
+
+    // the second run only touches clients 2 and 4
+    let mut second = PaymentEngine::new();
+    second.load_report(initial.as_bytes()).unwrap();
+    second
+        .process_csv(
+            "type,client,tx,amount\n\
+             withdrawal,2,4,5\n\
+             deposit,4,5,40\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    assert_eq!(
+        report_of(&second),
+        "client,available,held,total,locked\n\
+         1,10.0000,0.0000,10.0000,false\n\
+         2,15.0000,0.0000,15.0000,false\n\
+         3,30.0000,0.0000,30.0000,false\n\
+         4,40.0000,0.0000,40.0000,false\n"
+    );
+}
+
+#[test]
+fn report_with_inconsistent_total_rejected() {
+    let mut engine = PaymentEngine::new();
+    let report = "client,available,held,total,locked\n\
+                  1,10.0,5.0,10.0,false\n";
+    assert!(engine.load_report(report.as_bytes()).is_err());
+}
+
+#[test]
+fn audit_passes_after_dispute_and_chargeback() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             deposit,1,2,20\n\
+             withdrawal,1,3,5\n\
+             dispute,1,1,\n\
+             chargeback,1,1,\n\
+             deposit,2,4,20\n\
+             dispute,2,4,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    assert_eq!(engine.audit(), vec![]);
+}
+
+#[test]
+fn audit_flags_funds_without_recorded_deposits() {
+    let mut engine = PaymentEngine::new();
+    // the report's funds for client 1 came from nowhere this engine knows of
+    engine
+        .load_report(
+            "client,available,held,total,locked\n\
+             1,10.0,0.0,10.0,false\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,5\n\
+             deposit,2,2,20\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    assert_eq!(
+        engine.audit(),
+        vec![Diagnostic::FundsExceedRecorded {
+            client: 1,
+            total: Money::from_i64(15_0000),
+            recorded: Money::from_i64(5_0000),
+        }]
+    );
+}
+
+#[test]
+fn pretty_json_report_key_order() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,7,1,12.5\n\
+             dispute,7,1,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let mut out = Vec::new();
+    let options = ReportOptions {
+        pretty: true,
+        ..ReportOptions::default()
+    };
+    engine.write_json_report(&mut out, &options).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        r#"[
+  {
+    "client": 7,
+    "available": "0.0000",
+    "held": "12.5000",
+    "total": "12.5000",
+    "locked": false
+  }
+]"#
+    );
+}
+
+#[test]
+fn json_lines_report_one_account_per_line() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,2,1,12.5\n\
+             deposit,1,2,3\n\
+             dispute,2,1,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let mut out = Vec::new();
+    engine
+        .write_json_lines_report(&mut out, &ReportOptions::default())
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let accounts: Vec<serde_json::Value> = out
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        accounts,
+        [
+            serde_json::json!({
+                "client": 1, "available": "3.0000", "held": "0.0000", "total": "3.0000", "locked": false
+            }),
+            serde_json::json!({
+                "client": 2, "available": "0.0000", "held": "12.5000", "total": "12.5000", "locked": false
+            }),
+        ]
+    );
+    assert!(out.ends_with("}\n"));
+}
+
+#[test]
+fn forward_reference_warning() {
+    let records = "type,client,tx,amount\n\
+                   deposit,1,1,10\n\
+                   dispute,1,1,\n\
+                   dispute,1,2,\n\
+                   deposit,1,2,10\n";
+
+    // off by default
+    let mut engine = PaymentEngine::new();
+    engine.process_csv(records.as_bytes()).unwrap();
+    assert_eq!(engine.diagnostics(), []);
+
+    let mut engine = PaymentEngine::with_config(Config {
+        warn_forward_references: true,
+        ..Config::default()
+    });
+    engine.process_csv(records.as_bytes()).unwrap();
+    assert_eq!(engine.diagnostics(), [Diagnostic::ForwardReference(2)]);
+}
+
+#[test]
+fn report_with_compact_amounts() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,0.5\n\
+             deposit,2,2,1\n\
+             deposit,2,3,1.05\n\
+             dispute,2,3,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let options = ReportOptions {
+        compact: true,
+        ..ReportOptions::default()
+    };
+    let mut out = Vec::new();
+    engine.write_report(&mut out, &options).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "client,available,held,total,locked\n\
+         1,0.5,0.0,0.5,false\n\
+         2,1.0,1.05,2.05,false\n"
+    );
+    // and without, every amount has all four decimal places
+    assert_eq!(
+        report_of(&engine),
+        "client,available,held,total,locked\n\
+         1,0.5000,0.0000,0.5000,false\n\
+         2,1.0000,1.0500,2.0500,false\n"
+    );
+}
+
+#[test]
+fn report_without_total() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             deposit,2,2,20\n\
+             dispute,2,2,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let options = ReportOptions {
+        omit_total: true,
+        ..ReportOptions::default()
+    };
+    let mut out = Vec::new();
+    engine.write_report(&mut out, &options).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "client,available,held,locked\n\
+         1,10.0000,0.0000,false\n\
+         2,0.0000,20.0000,false\n"
+    );
+}
+
+#[test]
+fn closed_accounts_reported_and_snapshotted() {
+    let mut engine = PaymentEngine::with_config(Config {
+        policy: Policy {
+            allow_closing: true,
+            ..Policy::default()
+        },
+        ..Config::default()
+    });
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             deposit,2,2,20\n\
+             withdrawal,2,3,20\n\
+             close,2,4,\n\
+             deposit,2,5,5\n\
+             close,1,6,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let options = ReportOptions {
+        closed: true,
+        ..ReportOptions::default()
+    };
+    let mut out = Vec::new();
+    engine.write_report(&mut out, &options).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "client,available,held,total,locked,closed\n\
+         1,10.0000,0.0000,10.0000,false,false\n\
+         2,0.0000,0.0000,0.0000,false,true\n"
+    );
+    // without the column, the report is as before
+    assert_eq!(
+        report_of(&engine),
+        "client,available,held,total,locked\n\
+         1,10.0000,0.0000,10.0000,false\n\
+         2,0.0000,0.0000,0.0000,false\n"
+    );
+
+    let mut restored = PaymentEngine::new();
+    restored.restore_snapshot(engine.snapshot()).unwrap();
+    let closed: Vec<_> = restored
+        .accounts()
+        .filter(|account| account.is_closed())
+        .map(Account::client)
+        .collect();
+    assert_eq!(closed, [2]);
+}
+
+#[test]
+fn process_csv_from_bytes() {
+    let mut engine = PaymentEngine::new();
+    let bytes: &[u8] = b"type, client, tx, amount\n\
+                         deposit, 1, 1, 10.5\n\
+                         withdrawal, 1, 2, 0.5\n\
+                         deposit, 2, 3, 3\n";
+    engine.process_csv_bytes(bytes).unwrap();
+    assert_eq!(
+        balances_of(&engine),
+        [
+            (1, Money::from_i64(10_0000), Money::ZERO),
+            (2, Money::from_i64(3_0000), Money::ZERO),
+        ]
+    );
+}
+
+#[test]
+fn resolve_all_resolves_nothing_when_resolves_are_skipped() {
+    let mut engine = PaymentEngine::with_config(Config {
+        only_types: Some(["deposit", "dispute"].map(String::from).into()),
+        ..Config::default()
+    });
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             dispute,1,1,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    assert_eq!(engine.resolve_all(), []);
+    assert_eq!(engine.open_disputes().len(), 1);
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::ZERO, Money::from_i64(10_0000))]
+    );
+}
+
+#[test]
+fn resolve_all_open_disputes() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             deposit,1,2,20\n\
+             deposit,2,3,30\n\
+             deposit,2,4,40\n\
+             deposit,3,5,50\n\
+             dispute,1,1,\n\
+             dispute,1,2,\n\
+             dispute,2,3,\n\
+             dispute,2,4,\n\
+             chargeback,2,4,\n\
+             dispute,3,5,\n\
+             resolve,3,5,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let open = [
+        OpenDispute {
+            tx: 1,
+            client: 1,
+            amount: Money::from_i64(10_0000),
+        },
+        OpenDispute {
+            tx: 2,
+            client: 1,
+            amount: Money::from_i64(20_0000),
+        },
+        OpenDispute {
+            tx: 3,
+            client: 2,
+            amount: Money::from_i64(30_0000),
+        },
+    ];
+    assert_eq!(engine.open_disputes(), open);
+
+    // client 2's account is locked, but resolves are still allowed
+    assert_eq!(engine.resolve_all(), open);
+    assert_eq!(engine.open_disputes(), []);
+    assert_eq!(
+        balances_of(&engine),
+        [
+            (1, Money::from_i64(30_0000), Money::ZERO),
+            (2, Money::from_i64(30_0000), Money::ZERO),
+            (3, Money::from_i64(50_0000), Money::ZERO),
+        ]
+    );
+}
+
+#[test]
+fn iterate_over_accounts() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,3,1,10\n\
+             deposit,1,2,10\n\
+             deposit,2,3,10\n\
+             deposit,1,4,10\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let mut clients = Vec::new();
+    for account in &engine {
+        clients.push(account.client());
+    }
+    clients.sort_unstable();
+    assert_eq!(clients, [1, 2, 3]);
+}
+
+#[test]
+fn into_reports_sorted_by_client() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,3,1,10\n\
+             deposit,1,2,7.5\n\
+             deposit,2,3,20\n\
+             dispute,2,3,\n\
+             chargeback,2,3,\n\
+             dispute,3,1,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let balances = balances_of(&engine);
+    let reports = engine.into_reports();
+    assert_eq!(
+        reports,
+        [
+            AccountReport {
+                client: 1,
+                available: Money::from_i64(7_5000),
+                held: Money::ZERO,
+                total: Money::from_i64(7_5000),
+                locked: false,
+                closed: false,
+            },
+            AccountReport {
+                client: 2,
+                available: Money::ZERO,
+                held: Money::ZERO,
+                total: Money::ZERO,
+                locked: true,
+                closed: false,
+            },
+            AccountReport {
+                client: 3,
+                available: Money::ZERO,
+                held: Money::from_i64(10_0000),
+                total: Money::from_i64(10_0000),
+                locked: false,
+                closed: false,
+            },
+        ]
+    );
+    let reported: Vec<_> = reports
+        .iter()
+        .map(|report| (report.client, report.available, report.held))
+        .collect();
+    assert_eq!(reported, balances);
+}
+
+#[test]
+fn lone_dispute_opens_no_account() {
+    let records = "type,client,tx,amount\n\
+                   deposit,1,1,10\n\
+                   dispute,2,7,\n\
+                   chargeback,3,1,\n";
+    let mut engine = PaymentEngine::new();
+    engine.process_csv(records.as_bytes()).unwrap();
+    assert_eq!(
+        report_of(&engine),
+        "client,available,held,total,locked\n\
+         1,10.0000,0.0000,10.0000,false\n"
+    );
+
+    let mut engine = PaymentEngine::with_config(Config {
+        keep_implied_accounts: true,
+        ..Config::default()
+    });
+    engine.process_csv(records.as_bytes()).unwrap();
+    assert_eq!(
+        report_of(&engine),
+        "client,available,held,total,locked\n\
+         1,10.0000,0.0000,10.0000,false\n\
+         2,0.0000,0.0000,0.0000,false\n\
+         3,0.0000,0.0000,0.0000,false\n"
+    );
+}
+
+#[test]
+fn simulated_dispute_changes_nothing() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             deposit,1,2,2.5\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let before = engine.snapshot();
+
+    let dispute = Transaction::new(Action::new_dispute(), 1, 2);
+    assert_eq!(
+        engine.simulate(&dispute),
+        Ok(AccountReport {
+            client: 1,
+            available: Money::from_i64(10_0000),
+            held: Money::from_i64(2_5000),
+            total: Money::from_i64(12_5000),
+            locked: false,
+            closed: false,
+        })
+    );
+    assert_eq!(engine.snapshot(), before);
+
+    // rejected just as it'd be if applied
+    let unknown = Transaction::new(Action::new_dispute(), 1, 3);
+    assert_eq!(
+        engine.simulate(&unknown),
+        Err(account::Error::UnknownTxReference(3))
+    );
+    let duplicate = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(1_0000)).unwrap(),
+        1,
+        1,
+    );
+    assert_eq!(
+        engine.simulate(&duplicate),
+        Err(account::Error::DuplicateTransaction(1))
+    );
+
+    // and the simulation agrees with actually applying it
+    engine.process_transaction(&dispute).unwrap();
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(10_0000), Money::from_i64(2_5000))]
+    );
+    assert!(engine.tx_history().get(2).unwrap().disputed);
+}
+
+#[test]
+fn aliased_columns() {
+    let aliases = [
+        ("transaction_type", "type"),
+        ("customer", "client"),
+        ("transaction_id", "tx"),
+        ("value", "amount"),
+    ];
+    let mut engine = PaymentEngine::with_config(Config {
+        column_aliases: aliases
+            .iter()
+            .map(|&(from, to)| (from.to_owned(), to.to_owned()))
+            .collect(),
+        ..Config::default()
+    });
+    engine
+        .process_csv(
+            "customer,transaction_type,value,transaction_id\n\
+             1,deposit,10,1\n\
+             1,withdrawal,4,2\n\
+             2,deposit,3,3\n\
+             2,dispute,,3\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    assert_eq!(
+        balances_of(&engine),
+        [
+            (1, Money::from_i64(6_0000), Money::ZERO),
+            (2, Money::ZERO, Money::from_i64(3_0000)),
+        ]
+    );
+}
+
+#[test]
+fn dead_letter_rejected_records() {
+    let mut engine = PaymentEngine::new();
+    let mut dead_letter = Vec::new();
+    engine
+        .process_csv_with_dead_letter(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             deposit,1,1,10\n\
+             withdrawal,2,2,5\n\
+             dispute,1,1,\n\
+             dispute,1,1\n\
+             resolve,1,3,\n"
+                .as_bytes(),
+            &mut dead_letter,
+        )
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(dead_letter).unwrap(),
+        "type,client,tx,amount,reason\n\
+         deposit,1,1,10,Transaction already exists with id 1\n\
+         withdrawal,2,2,5,Insufficient funds for withdrawal in tx 2\n\
+         dispute,1,1,,Dispute attempted on transaction 1 that is already in dispute\n\
+         resolve,1,3,,Unknown transaction 3 referenced\n"
+    );
+}
+
+#[test]
+fn normalized_transactions_exclude_rejected() {
+    let mut engine = PaymentEngine::new();
+    let mut normalized = Vec::new();
+    engine
+        .process_csv_normalized(
+            "tx,type,client,amount\n\
+             1,Deposit,1,10.50\n\
+             1,deposit,1,10\n\
+             2,withdrawal,1,100\n\
+             3,WITHDRAWAL,1,0.5000\n\
+             1,dispute,1,\n\
+             9,resolve,1,\n"
+                .as_bytes(),
+            &mut normalized,
+        )
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(normalized).unwrap(),
+        "type,client,tx,amount\n\
+         deposit,1,1,10.5000\n\
+         withdrawal,1,3,0.5000\n\
+         dispute,1,1,\n"
+    );
+}
+
+#[test]
+fn trimming_padded_amounts() {
+    let records = "type,client,tx,amount\n\
+                   deposit,1,1, 10 \n";
+    for (trim, accepted) in [
+        (csv::Trim::None, false),
+        (csv::Trim::Headers, false),
+        (csv::Trim::Fields, true),
+        (csv::Trim::All, true),
+    ] {
+        let mut engine = PaymentEngine::with_config(Config {
+            trim,
+            ..Config::default()
+        });
+        let result = engine.process_csv(records.as_bytes());
+        assert_eq!(result.is_ok(), accepted, "{:?}", trim);
+        if accepted {
+            assert_eq!(
+                balances_of(&engine),
+                [(1, Money::from_i64(10_0000), Money::ZERO)]
+            );
+        }
+    }
+}
+
+#[test]
+fn keyed_transactions_apply_once() {
+    let mut engine = PaymentEngine::new();
+    let deposit = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(10_0000)).unwrap(),
+        1,
+        1,
+    );
+    for _ in 0..3 {
+        assert_eq!(
+            Ok(()),
+            engine.process_keyed_transaction("retry-me", &deposit)
+        );
+    }
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(10_0000), Money::ZERO)]
+    );
+
+    // a failure is cached too, even once it would've succeeded
+    let withdrawal = Transaction::new(
+        Action::try_new_withdrawal(Money::from_i64(15_0000)).unwrap(),
+        1,
+        2,
+    );
+    let insufficient = Err(account::Error::InsufficientFundsForWithdrawal(2));
+    assert_eq!(
+        insufficient,
+        engine.process_keyed_transaction("withdraw", &withdrawal)
+    );
+    let deposit = Transaction::new(
+        Action::try_new_deposit(Money::from_i64(10_0000)).unwrap(),
+        1,
+        3,
+    );
+    assert_eq!(Ok(()), engine.process_transaction(&deposit));
+    assert_eq!(
+        insufficient,
+        engine.process_keyed_transaction("withdraw", &withdrawal)
+    );
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(20_0000), Money::ZERO)]
+    );
+}
+
+#[test]
+fn snapshot_round_trip() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             deposit,1,2,20\n\
+             dispute,1,2,\n\
+             deposit,2,3,5\n\
+             dispute,2,3,\n\
+             chargeback,2,3,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let snapshot = engine.snapshot();
+    assert_eq!(snapshot.verify(), Ok(()));
+
+    let mut saved = Vec::new();
+    engine.save_snapshot(&mut saved).unwrap();
+    let mut resumed = PaymentEngine::new();
+    resumed.load_snapshot(saved.as_slice()).unwrap();
+    assert_eq!(resumed.snapshot(), snapshot);
+
+    // the history survived, so disputes exist to be resolved
+    resumed
+        .process_csv("type,client,tx,amount\nresolve,1,2,\n".as_bytes())
+        .unwrap();
+    assert_eq!(
+        balances_of(&resumed),
+        [
+            (1, Money::from_i64(30_0000), Money::ZERO),
+            (2, Money::ZERO, Money::ZERO),
+        ]
+    );
+}
+
+#[test]
+fn report_only_clients_changed_since_snapshot() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             deposit,2,2,20\n\
+             deposit,3,3,30\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let snapshot = engine.snapshot();
+
+    let mut resumed = PaymentEngine::new();
+    resumed.restore_snapshot(snapshot.clone()).unwrap();
+    resumed
+        .process_csv(
+            "type,client,tx,amount\n\
+             dispute,2,2,\n\
+             deposit,4,4,5\n\
+             withdrawal,3,5,100\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    // client 3's withdrawal was rejected, leaving it unchanged
+    let changed = resumed.changed_since(&snapshot);
+    assert_eq!(changed, HashSet::from([2, 4]));
+
+    let options = ReportOptions {
+        clients: Some(changed),
+        ..ReportOptions::default()
+    };
+    let mut report = Vec::new();
+    resumed.write_report(&mut report, &options).unwrap();
+    assert_eq!(
+        String::from_utf8(report).unwrap(),
+        "client,available,held,total,locked\n\
+         2,0.0000,20.0000,20.0000,false\n\
+         4,5.0000,0.0000,5.0000,false\n"
+    );
+}
+
+#[test]
+fn corrupt_snapshots_fail_verification() {
+    let valid = r#"{"accounts":[{"client":1,"available":"10.0","held":"5.0","total":"15.0","locked":true}],
+                    "history":[{"tx":1,"client":1,"kind":"deposit","amount":"0.0","disputed":false,"held":"0.0","charged_back":true},
+                               {"tx":2,"client":1,"kind":"deposit","amount":"5.0","disputed":true,"held":"5.0","charged_back":false}]}"#;
+    let snapshot: Snapshot = serde_json::from_str(valid).unwrap();
+    assert_eq!(snapshot.verify(), Ok(()));
+
+    for (from, to, violation) in [
+        (
+            r#""total":"15.0""#,
+            r#""total":"16.0""#,
+            Violation::TotalMismatch(1),
+        ),
+        (
+            r#""available":"10.0","held":"5.0""#,
+            r#""available":"20.0","held":"-5.0""#,
+            Violation::NegativeHeld(1),
+        ),
+        (
+            r#""disputed":false,"held":"0.0","charged_back":true"#,
+            r#""disputed":true,"held":"0.0","charged_back":true"#,
+            Violation::DisputedAfterChargeback(1),
+        ),
+        (
+            r#""amount":"0.0","disputed":false"#,
+            r#""amount":"1.0","disputed":false"#,
+            Violation::ChargebackNotZeroed(1),
+        ),
+    ] {
+        let corrupt = valid.replacen(from, to, 1);
+        let snapshot: Snapshot = serde_json::from_str(&corrupt).unwrap();
+        assert_eq!(snapshot.verify(), Err(violation));
+        let mut engine = PaymentEngine::new();
+        assert!(engine.load_snapshot(corrupt.as_bytes()).is_err());
+    }
+}
+
+#[test]
+fn engine_from_snapshot_file() {
+    let path = |name: &str| {
+        std::env::temp_dir().join(format!("payment_toy_{}_{}", std::process::id(), name))
+    };
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             deposit,2,2,5\n\
+             dispute,2,2,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let valid = path("from_snapshot.json");
+    engine.save_snapshot(File::create(&valid).unwrap()).unwrap();
+    let resumed = PaymentEngine::from_snapshot_file(&valid, Config::default()).unwrap();
+    std::fs::remove_file(&valid).unwrap();
+    assert_eq!(resumed.snapshot(), engine.snapshot());
+
+    let missing = path("from_snapshot_missing.json");
+    let error = PaymentEngine::from_snapshot_file(&missing, Config::default())
+        .err()
+        .unwrap();
+    assert!(matches!(&error, SnapshotError::Io(path, e)
+        if *path == missing && e.kind() == io::ErrorKind::NotFound));
+    assert!(error
+        .to_string()
+        .starts_with(&format!("can't read snapshot {}: ", missing.display())));
+
+    let corrupt = path("from_snapshot_corrupt.json");
+    std::fs::write(&corrupt, r#"{"accounts":[{"client":1"#).unwrap();
+    let error = PaymentEngine::from_snapshot_file(&corrupt, Config::default());
+    std::fs::remove_file(&corrupt).unwrap();
+    assert!(matches!(error, Err(SnapshotError::Json(_))));
+}
+
+#[test]
+fn report_limited_to_first_rows() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,4,1,1\n\
+             deposit,2,2,1\n\
+             deposit,3,3,1\n\
+             deposit,1,4,1\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let options = ReportOptions {
+        limit: Some(2),
+        ..ReportOptions::default()
+    };
+    let mut out = Vec::new();
+    engine.write_report(&mut out, &options).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "client,available,held,total,locked\n\
+         1,1.0000,0.0000,1.0000,false\n\
+         2,1.0000,0.0000,1.0000,false\n"
+    );
+    // the whole input was still processed
+    assert_eq!(engine.accounts().count(), 4);
+}
+
+#[test]
+fn report_locked_only() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             deposit,2,2,20\n\
+             deposit,3,3,30\n\
+             dispute,2,2,\n\
+             chargeback,2,2,\n\
+             dispute,3,3,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let options = ReportOptions {
+        locked_only: true,
+        ..ReportOptions::default()
+    };
+    let mut out = Vec::new();
+    engine.write_report(&mut out, &options).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "client,available,held,total,locked\n\
+         2,0.0000,0.0000,0.0000,true\n"
+    );
+    assert_eq!(engine.accounts().count(), 3);
+}
+
+#[test]
+fn previously_applied_ids_skipped() {
+    let mut engine = PaymentEngine::new();
+    engine.load_applied_ids("1\n2\n".as_bytes()).unwrap();
+    let mut dead_letter = Vec::new();
+    engine
+        .process_csv_with_dead_letter(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             deposit,1,2,10\n\
+             deposit,1,3,10\n\
+             withdrawal,1,4,5\n"
+                .as_bytes(),
+            &mut dead_letter,
+        )
+        .unwrap();
+    // skipped, rather than rejected as duplicates
+    assert!(dead_letter.is_empty());
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(5_0000), Money::ZERO)]
+    );
+    let mut applied = Vec::new();
+    engine.write_applied_ids(&mut applied).unwrap();
+    assert_eq!(String::from_utf8(applied).unwrap(), "1\n2\n3\n4\n");
+}
+
+#[test]
+fn merge_disjoint_shards() {
+    let mut evens = PaymentEngine::new();
+    evens
+        .process_csv_bytes(b"type,client,tx,amount\ndeposit,2,1,10\ndeposit,4,3,20\n")
+        .unwrap();
+    let mut odds = PaymentEngine::with_config(Config {
+        policy: Policy {
+            allow_adjustments: true,
+            ..Policy::default()
+        },
+        ..Config::default()
+    });
+    odds.process_csv_bytes(
+        b"type,client,tx,amount\ndeposit,1,2,5\ndispute,1,2,\nadjust,3,5,2\nresolve,3,9,\n",
+    )
+    .unwrap();
+    evens.merge(odds).unwrap();
+    assert_eq!(
+        report_of(&evens),
+        "client,available,held,total,locked\n\
+         1,0.0000,5.0000,5.0000,false\n\
+         2,10.0000,0.0000,10.0000,false\n\
+         3,2.0000,0.0000,2.0000,false\n\
+         4,20.0000,0.0000,20.0000,false\n"
+    );
+    // both shards' transactions are accounted for, as if processed by one engine
+    let reconciliation = evens.reconciliation();
+    assert_eq!(reconciliation.deposits, Money::from_i64(35_0000));
+    assert_eq!(reconciliation.adjustments, Money::from_i64(2_0000));
+    assert_eq!(
+        reconciliation.deposits + reconciliation.adjustments,
+        reconciliation.held + reconciliation.available
+    );
+    let stats = evens.stats();
+    assert_eq!((stats.read, stats.applied), (6, 5));
+    assert_eq!(stats.rejected.get("unknown_tx_reference"), Some(&1));
+    // the merged history still backs disputes
+    evens
+        .process_csv_bytes(b"type,client,tx,amount\nresolve,1,2,\ndispute,2,1,\n")
+        .unwrap();
+    assert_eq!(
+        balances_of(&evens),
+        [
+            (1, Money::from_i64(5_0000), Money::ZERO),
+            (2, Money::ZERO, Money::from_i64(10_0000)),
+            (3, Money::from_i64(2_0000), Money::ZERO),
+            (4, Money::from_i64(20_0000), Money::ZERO)
+        ]
+    );
+}
+
+#[test]
+fn merge_overlapping_shards_rejected() {
+    let mut first = PaymentEngine::new();
+    first
+        .process_csv_bytes(b"type,client,tx,amount\ndeposit,1,1,10\n")
+        .unwrap();
+    let mut same_client = PaymentEngine::new();
+    same_client
+        .process_csv_bytes(b"type,client,tx,amount\ndeposit,1,2,10\n")
+        .unwrap();
+    assert_eq!(first.merge(same_client), Err(MergeError::SharedClient(1)));
+    let mut same_tx = PaymentEngine::new();
+    same_tx
+        .process_csv_bytes(b"type,client,tx,amount\ndeposit,2,1,10\n")
+        .unwrap();
+    assert_eq!(first.merge(same_tx), Err(MergeError::SharedTx(1)));
+    // and neither was partially merged
+    assert_eq!(
+        report_of(&first),
+        "client,available,held,total,locked\n1,10.0000,0.0000,10.0000,false\n"
+    );
+}
+
+#[test]
+fn truncated_input_reported() {
+    let mut engine = PaymentEngine::new();
+    let error = engine
+        .process_csv_bytes(b"type,client,tx,amount\ndeposit,1,1,10\nwithdrawal,1,2")
+        .unwrap_err();
+    match error.kind() {
+        csv::ErrorKind::Io(error) => assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof),
+        _ => panic!("expected truncation, got {}", error),
+    }
+    assert!(error
+        .to_string()
+        .starts_with("input is truncated, the last record (line 3) is incomplete"));
+    // records before the truncated one were still applied
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(10_0000), Money::ZERO)]
+    );
+
+    // but a complete, malformed, last record is just malformed
+    let error = PaymentEngine::new()
+        .process_csv_bytes(b"type,client,tx,amount\nwithdrawal,1,2\n")
+        .unwrap_err();
+    assert!(matches!(error.kind(), csv::ErrorKind::Deserialize { .. }));
+}
+
+#[test]
+fn decimal_comma_with_semicolon_delimiter() {
+    let mut engine = PaymentEngine::with_config(Config {
+        delimiter: b';',
+        decimal_separator: ',',
+        ..Config::default()
+    });
+    engine
+        .process_csv(
+            "type;client;tx;amount\n\
+             deposit;1;1;1,2345\n\
+             deposit;1;2;10\n\
+             withdrawal;1;3;0,2\n\
+             dispute;1;2;\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(1_0345), Money::from_i64(10_0000))]
+    );
+
+    // a decimal point is no longer one
+    let error = engine
+        .process_csv("type;client;tx;amount\ndeposit;1;4;1.5\n".as_bytes())
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        r#"line 2: invalid digit in money field: "1.5""#
+    );
+    // nor are negative amounts accepted
+    assert!(engine
+        .process_csv("type;client;tx;amount\ndeposit;1;4;-1,5\n".as_bytes())
+        .is_err());
+}
+
+#[test]
+fn dangling_references_found_before_processing() {
+    let input = "type,client,tx,amount\n\
+                 dispute,1,2,\n\
+                 deposit,1,1,10\n\
+                 deposit,1,2,5\n\
+                 dispute,1,9,\n\
+                 resolve,1,1,\n\
+                 chargeback,2,7,\n";
+    let mut engine = PaymentEngine::new();
+    assert_eq!(
+        engine.dangling_references(input.as_bytes()).unwrap(),
+        [
+            Diagnostic::DanglingReference { line: 5, tx: 9 },
+            Diagnostic::DanglingReference { line: 7, tx: 7 },
+        ]
+    );
+    // nothing was applied
+    assert!(balances_of(&engine).is_empty());
+
+    // ids already recorded aren't dangling
+    engine
+        .process_csv("type,client,tx,amount\ndeposit,2,7,1\n".as_bytes())
+        .unwrap();
+    assert_eq!(
+        engine.dangling_references(input.as_bytes()).unwrap(),
+        [Diagnostic::DanglingReference { line: 5, tx: 9 }]
+    );
+}
+
+#[test]
+fn comment_lines_skipped() {
+    let input = "# exported by hand\n\
+                 type,client,tx,amount\n\
+                 deposit,1,1,10\n\
+                 # withdrawal,1,2,3\n\
+                 deposit,2,3,1\n\
+                 #\n\
+                 dispute,2,3,\n";
+    let mut engine = PaymentEngine::with_config(Config {
+        comment: Some(b'#'),
+        ..Config::default()
+    });
+    engine.process_csv(input.as_bytes()).unwrap();
+    assert_eq!(
+        balances_of(&engine),
+        [
+            (1, Money::from_i64(10_0000), Money::ZERO),
+            (2, Money::ZERO, Money::from_i64(1_0000)),
+        ]
+    );
+
+    // without a comment byte, the comments are just malformed records
+    assert!(PaymentEngine::new().process_csv(input.as_bytes()).is_err());
+}
+
+#[test]
+fn hash_seed_fixes_iteration_order() {
+    let mut input = String::from("type,client,tx,amount\n");
+    for tx in 1..=200 {
+        input += &format!("deposit,{},{},1\n", tx * 7 % 101, tx);
+    }
+    let order_of = |hash_seed| {
+        let mut engine = PaymentEngine::with_config(Config {
+            hash_seed,
+            ..Config::default()
+        });
+        engine.process_csv(input.as_bytes()).unwrap();
+        let clients: Vec<Client> = engine.accounts().map(Account::client).collect();
+        let txs: Vec<TxId> = engine.tx_history.entries().map(|(id, _)| id).collect();
+        (clients, txs)
+    };
+    let seeded = order_of(Some(42));
+    assert_eq!(seeded.0.len(), 101);
+    assert_eq!(seeded, order_of(Some(42)));
+    // 200 transactions, in one of 200! orders, are all but certain to be ordered differently
+    assert_ne!(seeded.1, order_of(Some(43)).1);
+}
+
+#[test]
+fn duplicate_headers_rejected() {
+    let mut engine = PaymentEngine::new();
+    let error = engine
+        .process_csv_bytes(b"type,client,tx,amount,amount\ndeposit,1,1,10,20\n")
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        r#"duplicate column "amount" in the header (columns 4 and 5)"#
+    );
+    // even unused columns, which serde would otherwise silently ignore
+    assert!(engine
+        .process_csv_bytes(b"type,client,tx,amount,note,note\ndeposit,1,1,10,a,b\n")
+        .is_err());
+    // and columns that only collide once renamed
+    let mut engine = PaymentEngine::with_config(Config {
+        column_aliases: HashMap::from([("value".to_owned(), "amount".to_owned())]),
+        ..Config::default()
+    });
+    assert!(engine
+        .process_csv_bytes(b"type,client,tx,amount,value\ndeposit,1,1,10,20\n")
+        .is_err());
+    assert!(balances_of(&engine).is_empty());
+}
+
+#[test]
+fn strict_headers_require_exact_columns() {
+    let strict = || {
+        PaymentEngine::with_config(Config {
+            strict_headers: true,
+            ..Config::default()
+        })
+    };
+    // by default, a missing amount column is read as short records, and extra columns ignored
+    let without_amount = b"type,client,tx\ndispute,1,1\n";
+    let with_note = b"type,client,tx,amount,note\ndeposit,1,1,10,a\n";
+    assert!(PaymentEngine::new()
+        .process_csv_bytes(without_amount)
+        .is_ok());
+    assert!(PaymentEngine::new().process_csv_bytes(with_note).is_ok());
+
+    let mut engine = strict();
+    let error = engine.process_csv_bytes(without_amount).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        r#"missing column "amount" in the header"#
+    );
+    let error = engine.process_csv_bytes(with_note).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        r#"unexpected column "note" in the header (column 5)"#
+    );
+    assert!(balances_of(&engine).is_empty());
+
+    // in any order, and once renamed by aliases
+    let mut engine = PaymentEngine::with_config(Config {
+        strict_headers: true,
+        column_aliases: HashMap::from([("value".to_owned(), "amount".to_owned())]),
+        ..Config::default()
+    });
+    engine
+        .process_csv_bytes(b"value,tx,type,client\n10,1,deposit,1\n")
+        .unwrap();
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(10_0000), Money::ZERO)]
+    );
+}
+
+#[test]
+fn strict_stops_at_first_rejection() {
+    let mut engine = PaymentEngine::new();
+    let error = engine
+        .process_csv_strict(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             dispute,1,7,\n\
+             deposit,1,2,5\n"
+                .as_bytes(),
+        )
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        StrictError::Rejected {
+            line: 3,
+            error: account::Error::UnknownTxReference(7)
+        }
+    ));
+    assert_eq!(error.tx(), Some(7));
+    // the deposit before it was applied, but not the one after
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(10_0000), Money::ZERO)]
+    );
+
+    let error = PaymentEngine::new()
+        .process_csv_strict("type,client,tx,amount\nbogus,1,1,\n".as_bytes())
+        .unwrap_err();
+    assert!(matches!(error, StrictError::Csv(_)));
+    assert_eq!(error.tx(), None);
+}
+
+#[test]
+fn reconciliation_sums() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10.5\n\
+             deposit,1,2,4\n\
+             withdrawal,1,3,2.25\n\
+             withdrawal,1,4,100\n\
+             deposit,2,5,20\n\
+             dispute,2,5,\n\
+             chargeback,2,5,\n\
+             deposit,3,6,7\n\
+             dispute,3,6,\n\
+             deposit,2,7,1\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let reconciliation = engine.reconciliation();
+    assert_eq!(
+        reconciliation,
+        Reconciliation {
+            deposits: Money::from_i64(41_5000),
+            withdrawals: Money::from_i64(2_2500),
+            charged_back: Money::from_i64(20_0000),
+            adjustments: Money::ZERO,
+            held: Money::from_i64(7_0000),
+            available: Money::from_i64(12_2500),
+        }
+    );
+    assert_eq!(
+        reconciliation.deposits - reconciliation.withdrawals - reconciliation.charged_back
+            + reconciliation.adjustments,
+        reconciliation.held + reconciliation.available
+    );
+    let mut out = Vec::new();
+    engine.write_reconciliation(&mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "deposits,withdrawals,charged_back,adjustments,held,available\n\
+         41.5000,2.2500,20.0000,0.0000,7.0000,12.2500\n"
+    );
+}
+
+/// Hands out its chunks one read at a time, setting `stop` as it hands out the second
+struct InterruptedReader {
+    chunks: Vec<&'static [u8]>,
+    stop: Arc<AtomicBool>,
+}
+
+impl io::Read for InterruptedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.chunks.is_empty() {
+            return Ok(0);
+        }
+        let chunk = self.chunks.remove(0);
+        if self.chunks.len() == 1 {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+        buf[..chunk.len()].copy_from_slice(chunk);
+        Ok(chunk.len())
+    }
+}
+
+#[test]
+fn stop_flag_interrupts_processing() {
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut engine = PaymentEngine::with_config(Config {
+        stop: Some(Arc::clone(&stop)),
+        ..Config::default()
+    });
+    let reader = InterruptedReader {
+        chunks: vec![
+            b"type,client,tx,amount\ndeposit,1,1,10\ndeposit,2,2,20\n",
+            b"deposit,1,3,5\nwithdrawal,2,4,5\n",
+            b"deposit,3,5,1\n",
+        ],
+        stop,
+    };
+    engine.process_csv(reader).unwrap();
+    // records read after the interrupt aren't applied
+    assert_eq!(
+        report_of(&engine),
+        "client,available,held,total,locked\n\
+         1,10.0000,0.0000,10.0000,false\n\
+         2,20.0000,0.0000,20.0000,false\n"
+    );
+    assert_eq!(engine.diagnostics(), [Diagnostic::Interrupted { line: 4 }]);
+}
+
+#[test]
+fn dispute_below_alert_threshold_warns() {
+    let mut engine = PaymentEngine::with_config(Config {
+        available_alert_threshold: Some(Money::ZERO - Money::from_i64(5_0000)),
+        ..Config::default()
+    });
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             deposit,2,2,10\n\
+             withdrawal,1,3,9\n\
+             withdrawal,2,4,1\n\
+             dispute,1,1,\n\
+             dispute,2,2,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    // client 1's dispute leaves -9, beyond the threshold, while client 2's leaves only -1
+    assert_eq!(
+        engine.diagnostics(),
+        [Diagnostic::AvailableBelowThreshold {
+            client: 1,
+            tx: 1,
+            available: Money::ZERO - Money::from_i64(9_0000),
+        }]
+    );
+    // and both disputes still applied
+    assert_eq!(
+        balances_of(&engine),
+        [
+            (
+                1,
+                Money::ZERO - Money::from_i64(9_0000),
+                Money::from_i64(10_0000)
+            ),
+            (
+                2,
+                Money::ZERO - Money::from_i64(1_0000),
+                Money::from_i64(10_0000)
+            )
+        ]
+    );
+}
+
+#[test]
+fn snapshots_mismatched_client() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv_bytes(b"type,client,tx,amount\ndeposit,1,1,10\ndeposit,3,2,10\n")
+        .unwrap();
+    let expected = engine.snapshot();
+    assert_eq!(engine.snapshot().first_mismatched_client(&expected), None);
+
+    engine
+        .process_csv_bytes(b"type,client,tx,amount\ndeposit,2,3,1\ndeposit,3,4,1\n")
+        .unwrap();
+    // client 2 is missing from the expected snapshot, and client 3 differs
+    assert_eq!(
+        engine.snapshot().first_mismatched_client(&expected),
+        Some(2)
+    );
+    assert_eq!(
+        expected.first_mismatched_client(&engine.snapshot()),
+        Some(2)
+    );
+}
+
+/// Records what had been written at each flush
+#[derive(Default)]
+struct FlushRecorder {
+    written: Vec<u8>,
+    flushes: Vec<String>,
+}
+
+impl io::Write for FlushRecorder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushes
+            .push(String::from_utf8(self.written.clone()).unwrap());
+        Ok(())
+    }
+}
+
+#[test]
+fn report_flushed_every_row() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv_bytes(b"type,client,tx,amount\ndeposit,1,1,1\ndeposit,2,2,2\n")
+        .unwrap();
+    let options = ReportOptions {
+        flush_every: Some(1),
+        ..ReportOptions::default()
+    };
+    let mut out = FlushRecorder::default();
+    engine.write_report(&mut out, &options).unwrap();
+    let header = "client,available,held,total,locked\n";
+    let first = format!("{}1,1.0000,0.0000,1.0000,false\n", header);
+    let both = format!("{}2,2.0000,0.0000,2.0000,false\n", first);
+    // once per row, and then again at the end
+    assert_eq!(out.flushes[..2], [first, both.clone()]);
+    assert!(out.flushes[2..].iter().all(|flushed| *flushed == both));
+
+    // by default, only once the whole report is written
+    let mut out = FlushRecorder::default();
+    engine
+        .write_report(&mut out, &ReportOptions::default())
+        .unwrap();
+    assert!(out.flushes.iter().all(|flushed| *flushed == both));
+}
+
+#[test]
+fn account_lookup_by_client() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv_bytes(b"type,client,tx,amount\ndeposit,1,1,1\ndeposit,5,2,5\ndeposit,9,3,9\n")
+        .unwrap();
+    for client in [1, 5, 9] {
+        let account = engine.account(client).unwrap();
+        assert_eq!(account.client(), client);
+        assert_eq!(account.available(), Money::from_i64(client as i64 * 1_0000));
+    }
+    assert!(engine.account(2).is_none());
+    assert!(engine.account(Client::MAX).is_none());
+}
+
+#[test]
+fn report_with_callback() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv_bytes(
+            b"type,client,tx,amount\ndeposit,2,1,3\ndeposit,1,2,10\ndispute,1,2,\ndeposit,3,3,1\ndispute,3,3,\nchargeback,3,3,\n",
+        )
+        .unwrap();
+    let mut calls = Vec::new();
+    engine.report_with(
+        &ReportOptions::default(),
+        |client, available, held, total, locked| {
+            calls.push((client, available, held, total, locked))
+        },
+    );
+    let m = Money::from_i64;
+    assert_eq!(
+        calls,
+        [
+            (1, Money::ZERO, m(10_0000), m(10_0000), false),
+            (2, m(3_0000), Money::ZERO, m(3_0000), false),
+            (3, Money::ZERO, Money::ZERO, Money::ZERO, true),
+        ]
+    );
+
+    let mut locked = Vec::new();
+    let options = ReportOptions {
+        locked_only: true,
+        ..ReportOptions::default()
+    };
+    engine.report_with(&options, |client, _, _, _, _| locked.push(client));
+    assert_eq!(locked, [3]);
+}
+
+#[test]
+fn stats_count_transactions_by_outcome() {
+    let mut engine = PaymentEngine::new();
+    engine.load_applied_ids("3\n".as_bytes()).unwrap();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             withdrawal,1,2,20\n\
+             deposit,1,3,5\n\
+             resolve,1,1,\n\
+             resolve,1,1,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let stats = engine.stats();
+    assert_eq!((stats.read, stats.applied, stats.skipped), (5, 1, 1));
+    assert_eq!(
+        stats.rejected,
+        BTreeMap::from([
+            ("cant_resolve_indisputed_tx", 2),
+            ("insufficient_funds_for_withdrawal", 1),
+        ])
+    );
+    assert_eq!(stats.rejected_total(), 3);
+
+    // a rolled back batch leaves no trace
+    let before = stats.clone();
+    let batch = [
+        Transaction::new(
+            Action::try_new_deposit(Money::from_i64(1_0000)).unwrap(),
+            2,
+            4,
+        ),
+        Transaction::new(Action::new_resolve(), 2, 4),
+    ];
+    assert!(engine.process_batch(&batch).is_err());
+    assert_eq!(engine.stats(), &before);
+}
+
+#[test]
+fn custom_bonus_credits_account() {
+    struct Bonus;
+    impl ActionHandler for Bonus {
+        fn apply(
+            &self,
+            account: &mut Account,
+            tx: &Transaction,
+            history: &mut account::TxHistory,
+        ) -> Result<(), account::Error> {
+            let Action::Custom {
+                amount: Some(amount),
+                ..
+            } = tx.action()
+            else {
+                return Err(account::Error::UnhandledAction(tx.id()));
+            };
+            let deposit = Transaction::new(
+                Action::try_new_deposit(amount).unwrap(),
+                tx.client(),
+                tx.id(),
+            );
+            account.process_transaction(&deposit, history)
+        }
+    }
+
+    let mut engine = PaymentEngine::new();
+    engine.register_action("bonus", Bonus);
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             Bonus,1,2,2.5\n\
+             bonus,1,3,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(12_5000), Money::ZERO)]
+    );
+    // the handler rejects bonuses without an amount
+    assert_eq!(engine.stats().rejected_total(), 1);
+
+    let unregistered = "type,client,tx,amount\ncashback,1,4,1\n";
+    assert!(engine.process_csv(unregistered.as_bytes()).is_err());
+}
+
+#[test]
+fn withdrawals_rounding_to_zero_rejected() {
+    let records = "type,client,tx,amount\n\
+                   deposit,1,1,10\n\
+                   withdrawal,1,2,0.00004\n";
+
+    // by default, the withdrawal is of nothing
+    let mut engine = PaymentEngine::new();
+    engine.process_csv(records.as_bytes()).unwrap();
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(10_0000), Money::ZERO)]
+    );
+
+    let config = || Config {
+        reject_rounded_to_zero: true,
+        ..Config::default()
+    };
+    // only the withdrawal is rejected, and the records after it are still applied
+    let mut engine = PaymentEngine::with_config(config());
+    engine
+        .process_csv(format!("{}withdrawal,1,3,2.5\n", records).as_bytes())
+        .unwrap();
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(7_5000), Money::ZERO)]
+    );
+    assert!(!engine.tx_history.contains(2));
+    assert_eq!(
+        engine.stats().rejected.get("withdrawal_rounds_to_zero"),
+        Some(&1)
+    );
+    assert_eq!(
+        engine.diagnostics()[0].to_string(),
+        "line 3 withdrawal 2 of \"0.00004\" rounds to zero at 4 decimal places, so was rejected"
+    );
+    // and written to the dead letter file, like any other rejection
+    let mut engine = PaymentEngine::with_config(config());
+    let mut dead_letter = Vec::new();
+    engine
+        .process_csv_with_dead_letter(records.as_bytes(), &mut dead_letter)
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(dead_letter).unwrap(),
+        "type,client,tx,amount,reason\n\
+         withdrawal,1,2,0.00004,Withdrawal 2's amount rounds to zero\n"
+    );
+
+    // withdrawals of zero as written, and those rounding to more than zero, are fine
+    let mut engine = PaymentEngine::with_config(config());
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             withdrawal,1,2,0.0000\n\
+             withdrawal,1,3,0.00005\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(9_9999), Money::ZERO)]
+    );
+}
+
+#[test]
+fn currency_scale_rounds_amounts_read_and_reported() {
+    let mut engine = PaymentEngine::with_config(Config {
+        currency: Some("JPY".to_owned()),
+        reject_rounded_to_zero: true,
+        ..Config::default()
+    });
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,1500.5\n\
+             withdrawal,1,2,0.4\n\
+             withdrawal,1,3,0.49995\n\
+             withdrawal,1,4,0.5\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    // rounded once, to whole yen, so 0.49995 isn't first rounded up to 0.5
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(1500_0000), Money::ZERO)]
+    );
+    assert_eq!(
+        engine.diagnostics()[0].to_string(),
+        "line 3 withdrawal 2 of \"0.4\" rounds to zero at 0 decimal places, so was rejected"
+    );
+    assert_eq!(
+        engine.stats().rejected.get("withdrawal_rounds_to_zero"),
+        Some(&2)
+    );
+
+    let options = ReportOptions {
+        currency: Some("JPY".to_owned()),
+        ..ReportOptions::default()
+    };
+    let mut out = Vec::new();
+    engine.write_report(&mut out, &options).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "client,available,held,total,locked\n1,1500,0,1500,false\n"
+    );
+}
+
+#[test]
+fn derived_totals_converted_by_rates() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv("type,client,tx,amount\ndeposit,1,1,10\n".as_bytes())
+        .unwrap();
+    let rates = currency::Rates::read("currency,rate\nEUR,1.08\n".as_bytes()).unwrap();
+    let mut options = ReportOptions {
+        currency: Some("EUR".to_owned()),
+        rates: Some(rates),
+        ..ReportOptions::default()
+    };
+    let mut out = Vec::new();
+    engine.write_json_report(&mut out, &options).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        r#"[{"client":1,"available":"10.00","held":"0.00","total":"10.00","locked":false,"derived_total":"10.8000"}]"#
+    );
+
+    // without a rate, nothing is written, not even the byte order mark
+    options.currency = Some("GBP".to_owned());
+    options.bom = true;
+    let mut out = Vec::new();
+    let error = engine.write_report(&mut out, &options).unwrap_err();
+    assert_eq!(error.to_string(), r#"no rate for currency "GBP""#);
+    assert!(out.is_empty());
+}
+
+#[cfg(any(feature = "client-u32", feature = "client-u64"))]
+#[test]
+fn client_ids_beyond_u16_when_widened() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,70000,1,10\n\
+             deposit,4000000000,2,5\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    assert_eq!(
+        report_of(&engine),
+        "client,available,held,total,locked\n\
+         70000,10.0000,0.0000,10.0000,false\n\
+         4000000000,5.0000,0.0000,5.0000,false\n"
+    );
+}
+
+#[cfg(not(any(feature = "client-u32", feature = "client-u64")))]
+#[test]
+fn client_ids_beyond_u16_rejected() {
+    let mut engine = PaymentEngine::new();
+    let records = "type,client,tx,amount\ndeposit,70000,1,10\n";
+    assert!(engine.process_csv(records.as_bytes()).is_err());
+}
+
+#[test]
+fn dump_history_with_dispute_flags() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,3,10\n\
+             withdrawal,1,2,2.5\n\
+             deposit,2,1,4\n\
+             dispute,1,3,\n\
+             withdrawal,2,4,100\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let mut out = Vec::new();
+    engine.write_history(&mut out).unwrap();
+    // the rejected withdrawal was never recorded
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "tx,kind,amount,disputed\n\
+         1,deposit,4.0000,false\n\
+         2,withdrawal,2.5000,false\n\
+         3,deposit,10.0000,true\n"
+    );
+    assert_eq!(engine.tx_history().entries().count(), 3);
+}
+
+#[test]
+fn reverse_back_to_starting_snapshot() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             deposit,2,2,20\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let start = engine.snapshot();
+
+    let period = "type,client,tx,amount\n\
+                  deposit,1,3,5\n\
+                  withdrawal,1,4,12.5\n\
+                  deposit,3,5,1\n\
+                  dispute,2,2,\n\
+                  withdraw_all,3,6,\n";
+    engine.process_csv(period.as_bytes()).unwrap();
+    assert_ne!(engine.snapshot(), start);
+    engine.reverse_csv(period.as_bytes()).unwrap();
+    // client 3's account remains open, though empty
+    assert_eq!(
+        balances_of(&engine),
+        [
+            (1, Money::from_i64(10_0000), Money::ZERO),
+            (2, Money::from_i64(20_0000), Money::ZERO),
+            (3, Money::ZERO, Money::ZERO),
+        ]
+    );
+    assert_eq!(engine.snapshot().history, start.history);
+
+    // chargebacks can't be reversed, so nothing is
+    let period = "type,client,tx,amount\n\
+                  deposit,1,7,5\n\
+                  dispute,2,2,\n\
+                  chargeback,2,2,\n";
+    engine.process_csv(period.as_bytes()).unwrap();
+    let after = engine.snapshot();
+    let error = engine.reverse_csv(period.as_bytes()).unwrap_err();
+    assert_eq!(error.tx(), Some(2));
+    assert_eq!(
+        error.to_string(),
+        "line 4 can't be reversed: Chargeback 2 can't be reversed"
+    );
+    assert_eq!(engine.snapshot(), after);
+}
+
+#[test]
+fn fractions_longer_than_maximum_rejected() {
+    let records = "type,client,tx,amount\n\
+                   deposit,1,1,10.123456\n\
+                   deposit,1,2,0.1234567\n";
+
+    // by default, they're rounded
+    let mut engine = PaymentEngine::new();
+    engine.process_csv(records.as_bytes()).unwrap();
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(10_2470), Money::ZERO)]
+    );
+
+    let mut engine = PaymentEngine::with_config(Config {
+        max_fraction_digits: Some(6),
+        ..Config::default()
+    });
+    let error = engine.process_csv(records.as_bytes()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "line 3: 7 fractional digits, more than the maximum of 6, in money field: \"0.1234567\""
+    );
+    // the six digit fraction before it was still rounded and applied
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(10_1235), Money::ZERO)]
+    );
+}
+
+#[test]
+fn max_memory_aborts_partway() {
+    let records: String = std::iter::once("type,client,tx,amount\n".to_owned())
+        .chain((1..=10).map(|n| format!("deposit,{},{},1.0\n", n, n)))
+        .collect();
+    // a deposit into a new account opens one account and records one transaction
+    let mut one = PaymentEngine::new();
+    one.process_csv("type,client,tx,amount\ndeposit,1,1,1.0\n".as_bytes())
+        .unwrap();
+    let per_deposit = one.estimated_memory();
+
+    let mut engine = PaymentEngine::with_config(Config {
+        max_memory: Some(3 * per_deposit),
+        ..Config::default()
+    });
+    let error = engine.process_csv(records.as_bytes()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "line 5: estimated memory use of {} bytes, for 3 accounts and 3 transactions, \
+             would exceed the maximum of {} bytes",
+            3 * per_deposit,
+            3 * per_deposit
+        )
+    );
+    // the deposits before it, within the maximum, remain applied
+    assert_eq!(balances_of(&engine).len(), 3);
+    assert!(engine.estimated_memory() <= 3 * per_deposit);
+}
+
+#[test]
+fn replayed_input_idempotent_with_policy() {
+    let records = "type,client,tx,amount\n\
+                   deposit,1,1,10.0\n\
+                   withdrawal,1,2,4.0\n";
+    let mut engine = PaymentEngine::with_config(Config {
+        policy: Policy {
+            idempotent_duplicates: true,
+            ..Policy::default()
+        },
+        ..Config::default()
+    });
+    engine.process_csv(records.as_bytes()).unwrap();
+    engine.process_csv(records.as_bytes()).unwrap();
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(6_0000), Money::ZERO)]
+    );
+    // the repeats are counted as skipped, not applied twice
+    assert_eq!((engine.stats().applied, engine.stats().skipped), (2, 2));
+    let totals = engine.reconciliation();
+    assert_eq!(totals.deposits, Money::from_i64(10_0000));
+    assert_eq!(totals.withdrawals, Money::from_i64(4_0000));
+    // a conflicting repeat is still a duplicate
+    assert_eq!(
+        engine.process_transaction(&Transaction::new(
+            Action::try_new_deposit(Money::from_i64(11_0000)).unwrap(),
+            1,
+            1
+        )),
+        Err(account::Error::DuplicateTransaction(1))
+    );
+}
+
+#[test]
+fn only_types_skip_the_rest() {
+    let records = "type,client,tx,amount\n\
+                   deposit,1,1,10.0\n\
+                   dispute,1,1,\n\
+                   withdrawal,1,2,4.0\n\
+                   chargeback,1,1,\n\
+                   dispute,2,1,\n";
+    let mut engine = PaymentEngine::with_config(Config {
+        only_types: Some(HashSet::from([
+            "deposit".to_owned(),
+            "withdrawal".to_owned(),
+        ])),
+        ..Config::default()
+    });
+    engine.process_csv(records.as_bytes()).unwrap();
+    // the disputes and chargeback neither held funds nor opened client 2's account
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(6_0000), Money::ZERO)]
+    );
+    assert!(engine.open_disputes().is_empty());
+    assert!(!engine.accounts().any(Account::is_locked));
+    assert_eq!((engine.stats().applied, engine.stats().skipped), (2, 3));
+}
+
+#[test]
+fn disputes_crossing_total_held_limit_rejected() {
+    let mut engine = PaymentEngine::with_config(Config {
+        max_total_held: Some(Money::from_i64(10_0000)),
+        ..Config::default()
+    });
+    engine
+        .process_csv_bytes(
+            b"type,client,tx,amount\n\
+              deposit,1,1,4.0\n\
+              deposit,2,2,5.0\n\
+              deposit,3,3,2.0\n\
+              deposit,3,4,1.0\n\
+              dispute,1,1,\n\
+              dispute,2,2,\n",
+        )
+        .unwrap();
+    // 9 of the 10 held, across two accounts
+    assert_eq!(
+        balances_of(&engine),
+        [
+            (1, Money::ZERO, Money::from_i64(4_0000)),
+            (2, Money::ZERO, Money::from_i64(5_0000)),
+            (3, Money::from_i64(3_0000), Money::ZERO),
+        ]
+    );
+    // a third account's dispute would cross the limit, so changes nothing
+    let crossing = Transaction::new(Action::new_dispute(), 3, 3);
+    assert_eq!(
+        engine.simulate(&crossing),
+        Err(account::Error::GlobalHeldLimitExceeded(3))
+    );
+    assert_eq!(
+        engine.process_transaction(&crossing),
+        Err(account::Error::GlobalHeldLimitExceeded(3))
+    );
+    assert_eq!(
+        balances_of(&engine)[2],
+        (3, Money::from_i64(3_0000), Money::ZERO)
+    );
+    assert!(!engine.tx_history().get(3).unwrap().disputed);
+    // reaching it exactly is fine
+    let reaching = Transaction::new(Action::new_dispute(), 3, 4);
+    assert_eq!(engine.process_transaction(&reaching), Ok(()));
+    // and resolving frees room for the rejected dispute
+    let resolve = Transaction::new(Action::new_resolve(), 2, 2);
+    assert_eq!(engine.process_transaction(&resolve), Ok(()));
+    assert_eq!(engine.process_transaction(&crossing), Ok(()));
+    assert_eq!(engine.reconciliation().held, Money::from_i64(7_0000));
+}
+
+#[test]
+fn digit_separators_stripped_only_when_allowed() {
+    let records = "type,client,tx,amount\n\
+                   deposit,1,1,1_000.5000\n";
+    let error = PaymentEngine::new()
+        .process_csv(records.as_bytes())
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "CSV deserialize error: record 1 (line: 2, byte: 22): \
+         invalid digit in money field: \"1_000.5000\""
+    );
+
+    let mut engine = PaymentEngine::with_config(Config {
+        allow_digit_separators: true,
+        ..Config::default()
+    });
+    engine.process_csv(records.as_bytes()).unwrap();
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(1000_5000), Money::ZERO)]
+    );
+
+    // along with a decimal comma
+    let mut engine = PaymentEngine::with_config(Config {
+        allow_digit_separators: true,
+        delimiter: b';',
+        decimal_separator: ',',
+        ..Config::default()
+    });
+    engine
+        .process_csv(b"type;client;tx;amount\ndeposit;1;1;1_000,5\n".as_slice())
+        .unwrap();
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(1000_5000), Money::ZERO)]
+    );
+}
+
+#[test]
+fn suspicious_sequences_warn_of_double_resolve() {
+    let records = "type,client,tx,amount\n\
+                   deposit,1,1,10.0\n\
+                   dispute,1,1,\n\
+                   resolve,1,1,\n\
+                   resolve,1,1,\n\
+                   dispute,1,1,\n\
+                   resolve,1,1,\n\
+                   dispute,2,5,\n\
+                   deposit,2,5,3.0\n";
+    let engine = PaymentEngine::new();
+    let suspicious = engine.suspicious_sequences(records.as_bytes()).unwrap();
+    assert_eq!(
+        suspicious,
+        [
+            Diagnostic::RepeatedReference {
+                line: 5,
+                tx: 1,
+                action: "resolve"
+            },
+            Diagnostic::ReferenceBeforeTransaction { line: 8, tx: 5 },
+        ]
+    );
+    assert_eq!(
+        suspicious[0].to_string(),
+        "line 5 repeats the resolve of transaction 1 straight after another"
+    );
+    // nothing was applied
+    assert!(balances_of(&engine).is_empty());
+}
+
+#[test]
+fn cross_client_dispute_and_chargeback_rejected() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             dispute,2,1,\n\
+             chargeback,2,1,\n\
+             resolve,2,1,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    // client 2 is left without an account at all, and client 1's deposit is untouched
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(10_0000), Money::ZERO)]
+    );
+    assert!(!engine.tx_history.get(1).unwrap().disputed);
+    assert_eq!(
+        engine.stats().rejected.get("unknown_tx_reference"),
+        Some(&3)
+    );
+}
+
+#[test]
+fn directly_built_negative_amounts_rejected() {
+    let mut engine = PaymentEngine::new();
+    let deposit = |amount| Transaction::new(Action::Deposit { amount }, 1, 1);
+    assert_eq!(
+        engine.process_transaction(&deposit(Money::from_i64(10_0000))),
+        Ok(())
+    );
+    for (tx, action) in [
+        (
+            2,
+            Action::Deposit {
+                amount: Money::from_i64(-5_0000),
+            },
+        ),
+        (
+            3,
+            Action::Withdrawal {
+                amount: Money::from_i64(-5_0000),
+            },
+        ),
+    ] {
+        assert_eq!(
+            engine.process_transaction(&Transaction::new(action, 1, tx)),
+            Err(account::Error::NegativeAmount(tx))
+        );
+    }
+    let partial = Action::PartialDispute {
+        amount: Money::from_i64(-1_0000),
+    };
+    assert_eq!(
+        engine.process_transaction(&Transaction::new(partial, 1, 1)),
+        Err(account::Error::NegativeAmount(1))
+    );
+    assert_eq!(
+        balances_of(&engine),
+        [(1, Money::from_i64(10_0000), Money::ZERO)]
+    );
+    assert!(!engine.tx_history.contains(2) && !engine.tx_history.contains(3));
+}
+
+#[test]
+fn failed_transfer_rolled_back() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10\n\
+             deposit,2,2,1\n\
+             dispute,2,2,\n\
+             chargeback,2,2,\n"
+                .as_bytes(),
+        )
+        .unwrap();
+    let before = engine.reconciliation();
+    let amount = Money::from_i64(4_0000);
+    // client 2 is locked, so the credit fails after the debit succeeds
+    let transfer = [
+        Transaction::new(Action::try_new_withdrawal(amount).unwrap(), 1, 3),
+        Transaction::new(Action::try_new_deposit(amount).unwrap(), 2, 4),
+    ];
+    assert_eq!(
+        engine.process_batch(&transfer),
+        Err(account::Error::AccountLockedFundsFrozen(4))
+    );
+    assert_eq!(
+        balances_of(&engine),
+        [
+            (1, Money::from_i64(10_0000), Money::ZERO),
+            (2, Money::ZERO, Money::ZERO),
+        ]
+    );
+    assert_eq!(engine.reconciliation(), before);
+    // and the debit's id is free again
+    assert!(!engine.tx_history.contains(3));
+
+    // a transfer to a new account applies in full
+    let transfer = [
+        Transaction::new(Action::try_new_withdrawal(amount).unwrap(), 1, 3),
+        Transaction::new(Action::try_new_deposit(amount).unwrap(), 3, 4),
+    ];
+    assert_eq!(engine.process_batch(&transfer), Ok(()));
+    assert_eq!(
+        balances_of(&engine),
+        [
+            (1, Money::from_i64(6_0000), Money::ZERO),
+            (2, Money::ZERO, Money::ZERO),
+            (3, amount, Money::ZERO),
+        ]
+    );
+
+    // while a failed batch opens no accounts
+    let transfer = [
+        Transaction::new(Action::try_new_deposit(amount).unwrap(), 4, 5),
+        Transaction::new(Action::try_new_withdrawal(amount).unwrap(), 1, 5),
+    ];
+    assert_eq!(
+        engine.process_batch(&transfer),
+        Err(account::Error::DuplicateTransaction(5))
+    );
+    assert!(engine.account(4).is_none());
+}
+
+#[test]
+fn ndjson_transactions_answered_per_line() {
+    let mut engine = PaymentEngine::new();
+    let mut replies = Vec::new();
+    engine
+        .process_ndjson(
+            r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "10"}
+{"type": "withdrawal", "client": 1, "tx": 2, "amount": "20"}
+
+{"type": "dispute", "client": 1, "tx": 1}
+not json
+"#
+            .as_bytes(),
+            &mut replies,
+        )
+        .unwrap();
+    let replies = String::from_utf8(replies).unwrap();
+    let replies: Vec<_> = replies.lines().collect();
+    assert_eq!(replies.len(), 4);
+    assert_eq!(
+        replies[0],
+        r#"{"client":1,"available":"10.0000","held":"0.0000","total":"10.0000","locked":false}"#
+    );
+    assert_eq!(
+        replies[1],
+        r#"{"error":"Insufficient funds for withdrawal in tx 2"}"#
+    );
+    assert_eq!(
+        replies[2],
+        r#"{"client":1,"available":"0.0000","held":"10.0000","total":"10.0000","locked":false}"#
+    );
+    assert!(replies[3].starts_with(r#"{"error":"#));
+}
+
+#[test]
+fn ndjson_negative_amounts_answered_with_error() {
+    let mut engine = PaymentEngine::new();
+    let mut replies = Vec::new();
+    engine
+        .process_ndjson(
+            r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "-10"}
+{"type": "withdrawal", "client": 1, "tx": 2, "amount": "-0.0001"}
+"#
+            .as_bytes(),
+            &mut replies,
+        )
+        .unwrap();
+    let replies = String::from_utf8(replies).unwrap();
+    assert_eq!(
+        replies,
+        r#"{"error":"invalid value: floating point `-10.0`, expected a positive amount of moneys"}
+{"error":"invalid value: string \"-0.0001\", expected a positive amount of moneys"}
+"#
+    );
+    assert!(engine.account(1).is_none());
+}
+
+#[cfg(all(unix, feature = "uds"))]
+#[test]
+fn serve_uds_replies_with_balance() {
+    use io::{BufRead, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let path = std::env::temp_dir().join(format!("payment_toy_{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    std::thread::spawn(move || PaymentEngine::new().serve_uds(&listener));
+
+    let mut client = UnixStream::connect(&path).unwrap();
+    writeln!(
+        client,
+        r#"{{"type": "deposit", "client": 3, "tx": 1, "amount": "2.5"}}"#
+    )
+    .unwrap();
+    let mut reply = String::new();
+    io::BufReader::new(&client).read_line(&mut reply).unwrap();
+    assert_eq!(
+        reply,
+        "{\"client\":3,\"available\":\"2.5000\",\"held\":\"0.0000\",\"total\":\"2.5000\",\"locked\":false}\n"
+    );
+    std::fs::remove_file(&path).unwrap();
+}
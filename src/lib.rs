@@ -0,0 +1,24 @@
+//! A toy payment engine for processing transaction records.
+//!
+// the money module is only at the top level without `std`, so link to wherever it is
+#![cfg_attr(
+    feature = "std",
+    doc = "Without the default `std` feature, only the [`Money`](account::Money) type is available."
+)]
+#![cfg_attr(
+    not(feature = "std"),
+    doc = "Without the default `std` feature, only the [`Money`](money::Money) type is available."
+)]
+#![cfg_attr(not(feature = "std"), no_std)]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod account;
+#[cfg(feature = "std")]
+pub mod engine;
+#[cfg(not(feature = "std"))]
+#[path = "account/money.rs"]
+pub mod money;
+
+#[cfg(feature = "std")]
+pub use engine::PaymentEngine;
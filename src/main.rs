@@ -1,50 +1,281 @@
-use account::{Account, Client, Transaction, TxHistory};
-use std::collections::HashMap;
+use cli::{Command, ErrorFormat, Format, Options, Replay};
+use payment_toy::{
+    account::currency::{ConversionError, Rates},
+    engine::{AccountReport, Diagnostic, ReverseError, Snapshot, StrictError},
+    PaymentEngine,
+};
 use std::fs::File;
-use std::path::PathBuf;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+#[cfg(feature = "ctrlc")]
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 
-mod account;
+mod cli;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() {
     // commandline interface
-    let mut args = std::env::args_os().skip(1);
-    let path = if let Some(path) = args.next() {
-        PathBuf::from(path)
-    } else {
-        return Err("Too few arguments! Expected one argument, the input CSV file.".into());
+    let command = match Command::from_args(std::env::args_os().skip(1)) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+    let mut error_format = ErrorFormat::Text;
+    let result = match command {
+        Command::Process(options) => {
+            error_format = options.error_format;
+            process(*options)
+        }
+        Command::VerifySnapshot(path) => verify_snapshot(&path),
+        Command::Replay(replay) => run_replay(&replay),
+        Command::ServeUds(socket) => serve_uds(&socket),
+        Command::ReportSchema => report_schema(),
     };
-    if args.next().is_some() {
-        return Err("Too many arguments! Expected one argument, the input CSV file.".into());
+    if let Err(e) = result {
+        match error_format {
+            ErrorFormat::Text => eprintln!("Error: {}", e),
+            ErrorFormat::Json => {
+                let tx = e
+                    .downcast_ref::<StrictError>()
+                    .and_then(StrictError::tx)
+                    .or_else(|| e.downcast_ref::<ReverseError>().and_then(ReverseError::tx));
+                eprintln!(
+                    "{}",
+                    serde_json::json!({ "error": e.to_string(), "tx": tx })
+                );
+            }
+        }
+        std::process::exit(1);
+    }
+}
+
+fn process(mut options: Options) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "ctrlc")]
+    {
+        options.config.stop = Some(stop_on_interrupt()?);
+    }
+    if let (Some(path), Some(currency)) = (&options.rates, &options.report.currency) {
+        // before processing, so that a missing rate doesn't waste the run
+        let rates = Rates::read(BufReader::new(File::open(path)?))?;
+        if rates.rate(currency).is_none() {
+            return Err(ConversionError::MissingRate(currency.clone()).into());
+        }
+        options.report.rates = Some(rates);
+    }
+    // the rest of the options are still needed after the engine takes its config
+    let mut engine = PaymentEngine::with_config(std::mem::take(&mut options.config));
+    if let Some(snapshot) = &options.snapshot {
+        engine.load_snapshot(BufReader::new(File::open(snapshot)?))?;
+    }
+    // kept to compare the accounts against after processing
+    let since_snapshot = match &options.since_snapshot {
+        Some(path) => {
+            let snapshot: Snapshot = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+            engine.restore_snapshot(snapshot.clone())?;
+            Some(snapshot)
+        }
+        None => None,
+    };
+    if let Some(applied_ids) = &options.applied_ids {
+        // a missing file means nothing has been applied yet
+        if applied_ids.exists() {
+            engine.load_applied_ids(BufReader::new(File::open(applied_ids)?))?;
+        }
+    }
+    if let Some(report) = &options.merge_into {
+        // a missing report is treated as an empty one
+        if report.exists() {
+            engine.load_report(File::open(report)?)?;
+        }
+    }
+
+    if options.validate_references {
+        let dangling = engine.dangling_references(BufReader::new(File::open(&options.input)?))?;
+        // the summary still fails the run when quiet
+        if !options.quiet {
+            for diagnostic in &dangling {
+                eprintln!("error: {}", diagnostic);
+            }
+        }
+        if !dangling.is_empty() {
+            return Err(
+                format!("{} dangling references, nothing processed", dangling.len()).into(),
+            );
+        }
+    }
+
+    if options.check_sequences && !options.quiet {
+        let input = BufReader::new(File::open(&options.input)?);
+        for diagnostic in engine.suspicious_sequences(input)? {
+            eprintln!("warning: {}", diagnostic);
+        }
     }
-    let file = File::open(&path)?;
 
     // process all transactions
-    let mut csv_in = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .flexible(true)
-        .trim(csv::Trim::All)
-        .from_reader(file);
-    let mut accounts = HashMap::<Client, Account>::new();
-    let mut tx_history = TxHistory::default();
-    for tx in csv_in.deserialize() {
-        let tx: Transaction = tx?;
-        let client = tx.client();
-        let account = accounts
-            .entry(client)
-            .or_insert_with(|| Account::new(client));
-        // ignore errors from process_transaction
-        account.process_transaction(&tx, &mut tx_history).ok();
+    let file = File::open(&options.input)?;
+    if options.normalize {
+        // written in place of the report
+        let mut out = stdout_and_tee(options.tee.as_deref())?;
+        engine.process_csv_normalized(file, &mut out)?;
+        out.flush()?;
+    } else if options.reverse {
+        engine.reverse_csv(file)?;
+    } else if options.strict {
+        engine.process_csv_strict(file)?;
+    } else if let Some(dead_letter) = &options.dead_letter {
+        engine.process_csv_with_dead_letter(file, File::create(dead_letter)?)?;
+    } else {
+        engine.process_csv(file)?;
+    }
+    if !options.quiet {
+        for diagnostic in engine.diagnostics() {
+            eprintln!("warning: {}", diagnostic);
+        }
+        if options.audit {
+            for diagnostic in engine.audit() {
+                eprintln!("warning: {}", diagnostic);
+            }
+        }
+    }
+    if let Some(reconciliation) = &options.reconciliation {
+        engine.write_reconciliation(File::create(reconciliation)?)?;
+    }
+    if let Some(history) = &options.dump_history {
+        engine.write_history(BufWriter::new(File::create(history)?))?;
+    }
+    if let Some(applied_ids) = &options.applied_ids {
+        engine.write_applied_ids(BufWriter::new(File::create(applied_ids)?))?;
+    }
+    if let Some(snapshot) = &options.save_snapshot {
+        let mut out = BufWriter::new(File::create(snapshot)?);
+        engine.save_snapshot(&mut out)?;
+        out.flush()?;
+    }
+
+    if let Some(snapshot) = &since_snapshot {
+        options.report.clients = Some(engine.changed_since(snapshot));
     }
 
     // generate report
-    let stdout = std::io::stdout();
-    let stdout = stdout.lock();
-    let mut csv_out = csv::WriterBuilder::new()
-        .has_headers(true)
-        .from_writer(stdout);
-    for (_client, account) in accounts.into_iter() {
-        csv_out.serialize(account)?;
+    if options.normalize {
+        // the normalized transactions were written instead
+    } else if options.count {
+        print!("{}", engine.stats());
+    } else if let Some(report) = &options.merge_into {
+        engine.write_report(File::create(report)?, &options.report)?;
+    } else {
+        let mut out = stdout_and_tee(options.tee.as_deref())?;
+        match options.format {
+            Format::Csv => engine.write_report(&mut out, &options.report)?,
+            Format::JsonLines => engine.write_json_lines_report(&mut out, &options.report)?,
+            Format::Json => {
+                engine.write_json_report(&mut out, &options.report)?;
+                writeln!(out)?;
+            }
+        }
+        out.flush()?;
+    }
+
+    // the report is partial, so exit as though killed by the interrupt
+    let interrupted = engine
+        .diagnostics()
+        .iter()
+        .any(|diagnostic| matches!(diagnostic, Diagnostic::Interrupted { .. }));
+    if interrupted {
+        std::process::exit(130);
     }
+    Ok(())
+}
+
+/// Stdout, and the `--tee` file if given
+fn stdout_and_tee(
+    tee: Option<&Path>,
+) -> std::io::Result<Tee<std::io::StdoutLock<'static>, BufWriter<File>>> {
+    let tee = match tee {
+        Some(path) => Some(BufWriter::new(File::create(path)?)),
+        None => None,
+    };
+    Ok(Tee(std::io::stdout().lock(), tee))
+}
+
+/// Writes everything to both `.0` and, if any, `.1`
+struct Tee<A, B>(A, Option<B>);
+
+impl<A: Write, B: Write> Write for Tee<A, B> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write_all(buf)?;
+        if let Some(tee) = &mut self.1 {
+            tee.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()?;
+        if let Some(tee) = &mut self.1 {
+            tee.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Installs a Ctrl-C handler that stops processing, so that a partial report is still written.
+/// A second Ctrl-C exits immediately.
+#[cfg(feature = "ctrlc")]
+fn stop_on_interrupt() -> Result<Arc<AtomicBool>, ctrlc::Error> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler_stop = Arc::clone(&stop);
+    ctrlc::set_handler(move || {
+        if handler_stop.swap(true, Ordering::Relaxed) {
+            std::process::exit(130);
+        }
+    })?;
+    Ok(stop)
+}
 
+/// Checks the snapshot at `path`, failing with its first violation
+fn verify_snapshot(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let snapshot: Snapshot = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    snapshot.verify()?;
+    println!("snapshot is valid");
     Ok(())
 }
+
+/// Prints the report's columns and their types as pretty JSON
+fn report_schema() -> Result<(), Box<dyn std::error::Error>> {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&AccountReport::schema())?
+    );
+    Ok(())
+}
+
+/// Replays transactions from a snapshot, failing with the first client not matching the expected
+fn run_replay(replay: &Replay) -> Result<(), Box<dyn std::error::Error>> {
+    let mut engine = PaymentEngine::new();
+    engine.load_snapshot(BufReader::new(File::open(&replay.from_snapshot)?))?;
+    engine.process_csv(File::open(&replay.input)?)?;
+    let expected: Snapshot = serde_json::from_reader(BufReader::new(File::open(&replay.expect)?))?;
+    if let Some(client) = engine.snapshot().first_mismatched_client(&expected) {
+        return Err(format!("client {} doesn't match the expected snapshot", client).into());
+    }
+    println!("replay matches the expected snapshot");
+    Ok(())
+}
+
+/// Applies transactions received over the Unix domain socket at `path`, until killed
+#[cfg(all(unix, feature = "uds"))]
+fn serve_uds(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = std::os::unix::net::UnixListener::bind(path)?;
+    PaymentEngine::new().serve_uds(&listener)?;
+    Ok(())
+}
+
+#[cfg(not(all(unix, feature = "uds")))]
+fn serve_uds(_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    Err("serve-uds requires building with the `uds` feature, on unix".into())
+}
@@ -0,0 +1,33 @@
+use payment_toy::{account::Money, PaymentEngine};
+
+fn money(amount: &str) -> Money {
+    amount.parse().unwrap()
+}
+
+#[test]
+fn balances_after_dispute_and_chargeback() {
+    let mut engine = PaymentEngine::new();
+    engine
+        .process_csv_bytes(
+            b"type,client,tx,amount\n\
+              deposit,1,1,10\n\
+              deposit,1,2,2.5\n\
+              dispute,1,1,\n\
+              deposit,2,3,7\n\
+              dispute,2,3,\n\
+              chargeback,2,3,\n",
+        )
+        .unwrap();
+    let mut balances: Vec<_> = engine
+        .accounts()
+        .map(|account| (account.client(), account.balances()))
+        .collect();
+    balances.sort_unstable_by_key(|&(client, _)| client);
+    assert_eq!(
+        balances,
+        [
+            (1, (money("2.5"), money("10"), false)),
+            (2, (Money::ZERO, Money::ZERO, true)),
+        ]
+    );
+}
@@ -0,0 +1,367 @@
+//! Runs the commandline interface end to end
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Writes `contents` to a fresh file in the temp directory, named after `name`
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("payment_toy_{}_{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_payment_toy"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn quiet_prints_only_the_report() {
+    let input = temp_file(
+        "quiet.csv",
+        "type,client,tx,amount\n\
+         dispute,1,2,\n\
+         deposit,1,1,10\n\
+         withdrawal,1,3,100\n",
+    );
+    let input = input.to_str().unwrap();
+    let expected_report = "client,available,held,total,locked\n1,10.0000,0.0000,10.0000,false\n";
+
+    // without --quiet, there's plenty to warn about
+    let loud = run(&["--warn-forward-references", "--audit", input]);
+    assert!(loud.status.success());
+    assert!(!loud.stderr.is_empty());
+
+    let quiet = run(&["--warn-forward-references", "--audit", "--quiet", input]);
+    assert!(quiet.status.success());
+    assert_eq!(String::from_utf8(quiet.stdout).unwrap(), expected_report);
+    assert_eq!(String::from_utf8(quiet.stderr).unwrap(), "");
+}
+
+#[test]
+fn quiet_dangling_references_print_only_the_failure() {
+    let input = temp_file(
+        "quiet_dangling.csv",
+        "type,client,tx,amount\n\
+         deposit,1,1,10\n\
+         dispute,1,2,\n\
+         chargeback,1,3,\n",
+    );
+    let input = input.to_str().unwrap();
+
+    let loud = run(&["--validate-references", input]);
+    assert!(!loud.status.success());
+    assert_eq!(String::from_utf8(loud.stderr).unwrap().lines().count(), 3);
+
+    let quiet = run(&["--validate-references", "--quiet", input]);
+    assert!(!quiet.status.success());
+    assert_eq!(quiet.stdout, b"");
+    assert_eq!(
+        String::from_utf8(quiet.stderr).unwrap(),
+        "Error: 2 dangling references, nothing processed\n"
+    );
+}
+
+#[test]
+fn verify_snapshot_subcommand() {
+    let input = temp_file(
+        "verify.csv",
+        "type,client,tx,amount\n\
+         deposit,1,1,10\n\
+         dispute,1,1,\n",
+    );
+    let snapshot =
+        std::env::temp_dir().join(format!("payment_toy_{}_verify.json", std::process::id()));
+    let snapshot = snapshot.to_str().unwrap();
+    let saved = run(&["--save-snapshot", snapshot, input.to_str().unwrap()]);
+    assert!(saved.status.success());
+
+    let valid = run(&["verify-snapshot", snapshot]);
+    assert!(valid.status.success());
+
+    let corrupt = std::fs::read_to_string(snapshot).unwrap().replace(
+        r#""available":"0.0000","held":"10.0000""#,
+        r#""available":"20.0000","held":"-10.0000""#,
+    );
+    std::fs::write(snapshot, corrupt).unwrap();
+    let invalid = run(&["verify-snapshot", snapshot]);
+    assert!(!invalid.status.success());
+    assert!(String::from_utf8(invalid.stderr)
+        .unwrap()
+        .contains("client 1 holds negative funds"));
+}
+
+#[test]
+fn tee_matches_stdout() {
+    let input = temp_file(
+        "tee.csv",
+        "type,client,tx,amount\n\
+         deposit,2,1,10\n\
+         deposit,1,2,5.5\n",
+    );
+    let tee = std::env::temp_dir().join(format!("payment_toy_{}_tee.json", std::process::id()));
+    let output = run(&[
+        "--tee",
+        tee.to_str().unwrap(),
+        "--format",
+        "json",
+        input.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    assert!(!output.stdout.is_empty());
+    assert_eq!(std::fs::read(&tee).unwrap(), output.stdout);
+}
+
+#[test]
+fn replay_subcommand() {
+    let temp = |name: &str| {
+        let path =
+            std::env::temp_dir().join(format!("payment_toy_{}_{}", std::process::id(), name));
+        path.to_str().unwrap().to_owned()
+    };
+    let (start, end) = (temp("replay_start.json"), temp("replay_end.json"));
+    let before = temp_file(
+        "replay_before.csv",
+        "type,client,tx,amount\ndeposit,1,1,10\n",
+    );
+    let replayed = temp_file(
+        "replay.csv",
+        "type,client,tx,amount\ndeposit,2,2,5\ndispute,1,1,\n",
+    );
+    let (before, replayed) = (before.to_str().unwrap(), replayed.to_str().unwrap());
+    assert!(run(&["--save-snapshot", &start, before]).status.success());
+    assert!(
+        run(&["--snapshot", &start, "--save-snapshot", &end, replayed])
+            .status
+            .success()
+    );
+
+    let matching = run(&[
+        "replay",
+        "--from-snapshot",
+        &start,
+        "--expect",
+        &end,
+        replayed,
+    ]);
+    assert!(matching.status.success());
+
+    // replaying onto the starting snapshot changes both clients, the first of which is reported
+    let mismatching = run(&[
+        "replay",
+        "--from-snapshot",
+        &start,
+        "--expect",
+        &start,
+        replayed,
+    ]);
+    assert!(!mismatching.status.success());
+    assert!(String::from_utf8(mismatching.stderr)
+        .unwrap()
+        .contains("client 1 doesn't match the expected snapshot"));
+}
+
+#[test]
+fn strict_rejection_as_json_error() {
+    let input = temp_file(
+        "strict.csv",
+        "type,client,tx,amount\n\
+         deposit,1,1,10\n\
+         withdrawal,1,2,100\n\
+         deposit,1,3,10\n",
+    );
+    let input = input.to_str().unwrap();
+    // rejections are ignored, unless strict
+    assert!(run(&["--error-format", "json", input]).status.success());
+
+    let strict = run(&["--strict", "--error-format", "json", input]);
+    assert_eq!(strict.status.code(), Some(1));
+    assert!(strict.stdout.is_empty());
+    let error: serde_json::Value = serde_json::from_slice(&strict.stderr).unwrap();
+    assert_eq!(
+        error,
+        serde_json::json!({
+            "error": "line 3 was rejected: Insufficient funds for withdrawal in tx 2",
+            "tx": 2,
+        })
+    );
+
+    // failures without a transaction to blame have a null tx
+    let missing = run(&["--error-format", "json", "no/such/file.csv"]);
+    let error: serde_json::Value = serde_json::from_slice(&missing.stderr).unwrap();
+    assert!(error["error"].is_string());
+    assert!(error["tx"].is_null());
+}
+
+#[test]
+fn count_prints_only_the_counts() {
+    let input = temp_file(
+        "count.csv",
+        "type,client,tx,amount\n\
+         deposit,1,1,10\n\
+         deposit,1,1,10\n\
+         withdrawal,1,2,100\n\
+         withdrawal,1,3,1\n\
+         dispute,1,9,\n\
+         dispute,1,1,\n\
+         chargeback,1,1,\n\
+         deposit,1,4,1\n",
+    );
+    let count = run(&["--count", input.to_str().unwrap()]);
+    assert!(count.status.success());
+    assert_eq!(
+        String::from_utf8(count.stdout).unwrap(),
+        "read: 8\n\
+         applied: 4\n\
+         skipped: 0\n\
+         rejected: 4\n  \
+         account_locked_funds_frozen: 1\n  \
+         duplicate_transaction: 1\n  \
+         insufficient_funds_for_withdrawal: 1\n  \
+         unknown_tx_reference: 1\n"
+    );
+}
+
+#[test]
+fn output_bom_prefixes_report() {
+    let input = temp_file("bom.csv", "type,client,tx,amount\ndeposit,1,1,10\n");
+    let input = input.to_str().unwrap();
+    let report = "client,available,held,total,locked\n1,10.0000,0.0000,10.0000,false\n";
+
+    let plain = run(&[input]);
+    assert_eq!(plain.stdout, report.as_bytes());
+
+    let bom = run(&["--output-bom", input]);
+    assert!(bom.status.success());
+    assert_eq!(&bom.stdout[..3], b"\xEF\xBB\xBF");
+    assert_eq!(&bom.stdout[3..], report.as_bytes());
+}
+
+#[test]
+fn compact_trims_trailing_zeros() {
+    let input = temp_file("compact.csv", "type,client,tx,amount\ndeposit,1,1,10.5\n");
+    let input = input.to_str().unwrap();
+
+    let fixed = run(&[input]);
+    assert_eq!(
+        String::from_utf8(fixed.stdout).unwrap(),
+        "client,available,held,total,locked\n1,10.5000,0.0000,10.5000,false\n"
+    );
+
+    let compact = run(&["--compact", input]);
+    assert!(compact.status.success());
+    assert_eq!(
+        String::from_utf8(compact.stdout).unwrap(),
+        "client,available,held,total,locked\n1,10.5,0.0,10.5,false\n"
+    );
+}
+
+#[test]
+fn currency_rounds_and_reports_at_its_scale() {
+    let input = temp_file(
+        "currency.csv",
+        "type,client,tx,amount\ndeposit,1,1,1.2345\ndeposit,2,2,1500.5\n",
+    );
+    let input = input.to_str().unwrap();
+
+    let dinars = run(&["--currency", "bhd", input]);
+    assert!(dinars.status.success());
+    assert_eq!(
+        String::from_utf8(dinars.stdout).unwrap(),
+        "client,available,held,total,locked\n\
+         1,1.235,0.000,1.235,false\n\
+         2,1500.500,0.000,1500.500,false\n"
+    );
+
+    let invalid = run(&["--currency", "¥", input]);
+    assert!(!invalid.status.success());
+    assert!(String::from_utf8(invalid.stderr)
+        .unwrap()
+        .starts_with("--currency expects a currency code, such as JPY, got \"¥\"!"));
+}
+
+#[test]
+fn rates_add_derived_totals() {
+    let input = temp_file(
+        "rates.csv",
+        "type,client,tx,amount\ndeposit,1,1,10\ndeposit,2,2,2.5\ndispute,2,2,\n",
+    );
+    let input = input.to_str().unwrap();
+    let rates = temp_file("rates_table.csv", "currency,rate\nEUR,1.08\nUSD,1\n");
+    let rates = rates.to_str().unwrap();
+
+    let euros = run(&["--currency", "EUR", "--rates", rates, input]);
+    assert!(euros.status.success());
+    assert_eq!(
+        String::from_utf8(euros.stdout).unwrap(),
+        "client,available,held,total,locked,derived_total\n\
+         1,10.00,0.00,10.00,false,10.8000\n\
+         2,0.00,2.50,2.50,false,2.7000\n"
+    );
+
+    // a currency without a rate fails before processing, and prints no report
+    let pounds = run(&["--currency", "GBP", "--rates", rates, input]);
+    assert!(!pounds.status.success());
+    assert!(pounds.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(pounds.stderr).unwrap(),
+        "Error: no rate for currency \"GBP\"\n"
+    );
+
+    // as does a total too large to convert, after processing
+    let large = temp_file(
+        "rates_large.csv",
+        "type,client,tx,amount\ndeposit,1,1,1\ndeposit,2,2,900000000000000\n",
+    );
+    let large = run(&[
+        "--currency",
+        "EUR",
+        "--rates",
+        rates,
+        large.to_str().unwrap(),
+    ]);
+    assert!(!large.status.success());
+    assert!(large.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(large.stderr).unwrap(),
+        "Error: converted balances too large to be represented\n"
+    );
+
+    let without_currency = run(&["--rates", rates, input]);
+    assert!(!without_currency.status.success());
+    assert!(String::from_utf8(without_currency.stderr)
+        .unwrap()
+        .starts_with("--rates requires --currency!"));
+}
+
+#[test]
+fn report_schema_lists_columns_with_types() {
+    let schema = run(&["report-schema", "json"]);
+    assert!(schema.status.success());
+    let schema: serde_json::Value = serde_json::from_slice(&schema.stdout).unwrap();
+    let columns: Vec<(&str, &str)> = schema["columns"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|column| {
+            (
+                column["name"].as_str().unwrap(),
+                column["type"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        columns,
+        [
+            ("client", "integer"),
+            ("available", "decimal"),
+            ("held", "decimal"),
+            ("total", "decimal"),
+            ("locked", "boolean"),
+        ]
+    );
+    assert_eq!(schema["columns"][1]["scale"], 4);
+
+    assert!(!run(&["report-schema", "xml"]).status.success());
+}
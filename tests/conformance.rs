@@ -0,0 +1,42 @@
+//! Runs every fixture in `tests/fixtures`, a directory per case holding an `input.csv` of
+//! transactions and the `expected.csv` report, written with all four decimal places.
+//!
+//! Add a regression case by adding a directory.
+use payment_toy::{engine::ReportOptions, PaymentEngine};
+use std::fs;
+use std::path::Path;
+
+/// Processes the case's input, returning the report if it doesn't match the expected report
+fn mismatched_report(case: &Path) -> Option<String> {
+    let input = fs::read(case.join("input.csv")).unwrap();
+    let expected = fs::read_to_string(case.join("expected.csv")).unwrap();
+    let mut engine = PaymentEngine::new();
+    engine.process_csv_bytes(&input).unwrap();
+    let mut report = Vec::new();
+    engine
+        .write_report(&mut report, &ReportOptions::default())
+        .unwrap();
+    let report = String::from_utf8(report).unwrap();
+    (report != expected).then_some(report)
+}
+
+#[test]
+fn fixtures_match_expected_reports() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut cases: Vec<_> = fs::read_dir(fixtures)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "no fixtures found");
+
+    let mismatches: Vec<String> = cases
+        .iter()
+        .filter_map(|case| {
+            let report = mismatched_report(case)?;
+            Some(format!("{}, reported:\n{}", case.display(), report))
+        })
+        .collect();
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
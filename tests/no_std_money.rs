@@ -0,0 +1,17 @@
+//! Compiles the money module without `std`, only `core` and `alloc`.
+#![no_std]
+extern crate alloc;
+
+#[allow(dead_code)]
+#[path = "../src/account/money.rs"]
+mod money;
+
+use money::Money;
+
+#[test]
+fn money_arithmatic_without_std() {
+    let ten: Money = "10.5".parse().unwrap();
+    let one: Money = "1".parse().unwrap();
+    assert_eq!(ten - one + one, ten);
+    assert!((one - ten).is_negative());
+}